@@ -0,0 +1,218 @@
+extern crate ansi_term;
+
+use std::collections::BTreeMap;
+
+use self::ansi_term::Colour::{Cyan, Green, Red, Yellow};
+use parser::{
+    self,
+    parsed::{self, Metric, PathStats},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Parser error: {0}")]
+    Parse(#[from] parser::Error),
+    #[error("{0} call path(s) regressed by more than {1}% in {2}")]
+    Regression(usize, f64, &'static str),
+}
+
+/// Compares two parsed logs by call path.
+#[derive(clap::Parser)]
+pub struct Cli {
+    /// Parsed log from before the change.
+    pub before: String,
+
+    /// Parsed log from after the change.
+    pub after: String,
+
+    /// Fail when the measurement of a call path grows by more than this percentage.
+    #[clap(short, long)]
+    pub threshold: Option<f64>,
+
+    /// Measurement the threshold applies to.
+    #[clap(short, long, value_enum, default_value_t = Metric::Global)]
+    pub metric: Metric,
+
+    /// Also print call paths that did not change.
+    #[clap(short, long)]
+    pub all: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(PathStats),
+    Removed(PathStats),
+    Matched(PathStats, PathStats),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathDiff {
+    pub path: String,
+    pub change: Change,
+}
+
+impl PathDiff {
+    /// Relative growth of `metric` in percent, if the path exists on both sides
+    /// and was not free before.
+    pub fn percent(&self, metric: Metric) -> Option<f64> {
+        match &self.change {
+            Change::Matched(before, after) if metric.of(&before.report) != 0 => {
+                let before = metric.of(&before.report) as f64;
+                let after = metric.of(&after.report) as f64;
+                Some((after - before) / before.abs() * 100.0)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        matches!(&self.change, Change::Matched(before, after) if before == after)
+    }
+}
+
+/// Matches call paths of both sides, in path order.
+pub fn diff(
+    before: &BTreeMap<String, PathStats>,
+    after: &BTreeMap<String, PathStats>,
+) -> Vec<PathDiff> {
+    let mut paths = before.keys().chain(after.keys()).collect::<Vec<_>>();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .map(|path| PathDiff {
+            path: path.clone(),
+            change: match (before.get(path), after.get(path)) {
+                (Some(before), Some(after)) => Change::Matched(before.clone(), after.clone()),
+                (Some(before), None) => Change::Removed(before.clone()),
+                (None, Some(after)) => Change::Added(after.clone()),
+                (None, None) => unreachable!(),
+            },
+        })
+        .collect()
+}
+
+impl Cli {
+    pub fn run(&self) -> Result<(), Error> {
+        let before = parsed::by_path(&parsed::read(&self.before)?);
+        let after = parsed::by_path(&parsed::read(&self.after)?);
+
+        let mut regressions = 0;
+        for path_diff in diff(&before, &after) {
+            if !self.all && path_diff.is_unchanged() {
+                continue;
+            }
+            let regressed = match (self.threshold, path_diff.percent(self.metric)) {
+                (Some(threshold), Some(percent)) => percent > threshold,
+                _ => false,
+            };
+            if regressed {
+                regressions += 1;
+            }
+            self.print(&path_diff, regressed);
+        }
+
+        match self.threshold {
+            Some(threshold) if regressions > 0 => Err(Error::Regression(
+                regressions,
+                threshold,
+                self.metric.name(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn print_one(label: &str, path: &str, stats: &PathStats) {
+        println!(
+            "{} {} (calls {})",
+            Yellow.paint(label),
+            Cyan.paint(path),
+            stats.calls
+        );
+        for metric in Metric::ALL {
+            println!("    {:<12} {:>10}", metric.name(), metric.of(&stats.report));
+        }
+    }
+
+    fn print(&self, path_diff: &PathDiff, regressed: bool) {
+        match &path_diff.change {
+            Change::Added(stats) => Self::print_one("added", &path_diff.path, stats),
+            Change::Removed(stats) => Self::print_one("removed", &path_diff.path, stats),
+            Change::Matched(before, after) => {
+                println!(
+                    "{} {} (calls {} -> {}){}",
+                    Yellow.paint("changed"),
+                    Cyan.paint(&path_diff.path),
+                    before.calls,
+                    after.calls,
+                    if regressed {
+                        Red.bold().paint(" REGRESSION").to_string()
+                    } else {
+                        String::new()
+                    }
+                );
+                for metric in Metric::ALL {
+                    let (before, after) = (metric.of(&before.report), metric.of(&after.report));
+                    let delta = format!("{:+}", after - before);
+                    let percent = path_diff
+                        .percent(metric)
+                        .map_or_else(|| "n/a".to_string(), |p| format!("{:+.1}%", p));
+                    let colour = if after > before { Red } else { Green };
+                    println!(
+                        "    {:<12} {:>10} -> {:>10} {:>10} {:>9}",
+                        metric.name(),
+                        before,
+                        after,
+                        colour.paint(format!("{:>10}", delta)),
+                        colour.paint(format!("{:>9}", percent)),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::consumption::Report;
+
+    fn stats(calls: u32, global: i32) -> PathStats {
+        PathStats {
+            calls,
+            report: Report {
+                global,
+                ..Report::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = vec![
+            ("P".to_string(), stats(1, 1000)),
+            ("P/a".to_string(), stats(1, 400)),
+            ("P/b".to_string(), stats(2, 100)),
+        ]
+        .into_iter()
+        .collect();
+        let after = vec![
+            ("P".to_string(), stats(1, 1100)),
+            ("P/a".to_string(), stats(1, 500)),
+            ("P/c".to_string(), stats(1, 50)),
+        ]
+        .into_iter()
+        .collect();
+
+        let diffs = diff(&before, &after);
+        assert_eq!(
+            diffs.iter().map(|d| d.path.as_str()).collect::<Vec<_>>(),
+            vec!["P", "P/a", "P/b", "P/c"]
+        );
+        assert_eq!(diffs[0].percent(Metric::Global), Some(10.0));
+        assert_eq!(diffs[1].percent(Metric::Global), Some(25.0));
+        assert_eq!(diffs[1].percent(Metric::Local), None);
+        assert!(matches!(diffs[2].change, Change::Removed(_)));
+        assert!(matches!(diffs[3].change, Change::Added(_)));
+    }
+}
@@ -0,0 +1,3 @@
+extern crate clap;
+pub mod diff;
+pub mod parser;
@@ -1,11 +1,14 @@
 extern crate clap;
+extern crate sol_dev_cli;
 extern crate tracing_subscriber;
-mod parser;
+use sol_dev_cli::{diff, parser};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Parser error: {0}")]
     Parse(#[from] parser::Error),
+    #[error("Diff error: {0}")]
+    Diff(#[from] diff::Error),
 }
 
 use clap::Parser;
@@ -28,12 +31,14 @@ pub fn init_env_logger() {
 #[derive(clap::Parser)]
 pub enum Command {
     Parse(parser::Cli),
+    Diff(diff::Cli),
 }
 
 impl Command {
     pub fn run(&self) -> Result<(), Error> {
         match &self {
             Command::Parse(cmd) => cmd.run()?,
+            Command::Diff(cmd) => cmd.run()?,
         }
         Ok(())
     }
@@ -49,6 +54,7 @@ impl Cli {
     pub fn run(&self) -> Result<(), Error> {
         match &self.cmd {
             Command::Parse(cmd) => Ok(cmd.run()?),
+            Command::Diff(cmd) => Ok(cmd.run()?),
         }
     }
 }
@@ -57,5 +63,6 @@ fn main() {
     init_env_logger();
     if let Err(e) = Cli::parse().cmd.run() {
         tracing::error!("Error: {}", e);
+        std::process::exit(1);
     }
 }
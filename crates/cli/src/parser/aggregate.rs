@@ -0,0 +1,240 @@
+extern crate serde;
+
+use std::collections::BTreeMap;
+
+use self::serde::Serialize;
+
+use super::{
+    consumption::Consumer,
+    log::{Entry, InnerLog},
+    parsed,
+};
+
+/// The `local` and `global` measurements of every call of one function or call path.
+#[derive(Debug, Clone, Default)]
+struct Samples {
+    local: Vec<i32>,
+    global: Vec<i32>,
+}
+
+impl Samples {
+    fn stats(&self) -> Stats {
+        Stats {
+            calls: self.local.len(),
+            local: Distribution::new(&self.local),
+            global: Distribution::new(&self.global),
+        }
+    }
+}
+
+/// Collects measurements across many trees, by function name and by call path.
+#[derive(Debug, Clone, Default)]
+pub struct Aggregate {
+    by_name: BTreeMap<String, Samples>,
+    by_path: BTreeMap<String, Samples>,
+}
+
+impl Aggregate {
+    pub fn new() -> Aggregate {
+        Aggregate::default()
+    }
+
+    pub fn add_entry(&mut self, entry: &Entry) {
+        for inner_log in entry.inner_logs() {
+            self.add(inner_log, "");
+        }
+    }
+
+    /// Invokes are grouped by program and instruction, see `Invoke::label`, as in
+    /// `parsed::by_path`.
+    fn add(&mut self, inner_log: &InnerLog, parent: &str) {
+        let id = match inner_log {
+            InnerLog::Invoke(invoke) => invoke.label(),
+            inner_log => match inner_log.id() {
+                Some(id) => id.to_string(),
+                None => return,
+            },
+        };
+        let path = parsed::join(parent, &id);
+        let (local, global) = (inner_log.local_ex_log(), inner_log.global_ex_log());
+        for samples in [
+            self.by_name.entry(id.clone()).or_default(),
+            self.by_path.entry(path.clone()).or_default(),
+        ] {
+            samples.local.push(local);
+            samples.global.push(global);
+        }
+        for child in inner_log.inner_logs() {
+            self.add(child, &path);
+        }
+    }
+
+    pub fn merge(mut self, other: Aggregate) -> Aggregate {
+        for (mine, theirs) in [
+            (&mut self.by_name, other.by_name),
+            (&mut self.by_path, other.by_path),
+        ] {
+            for (key, samples) in theirs {
+                let entry = mine.entry(key).or_default();
+                entry.local.extend(samples.local);
+                entry.global.extend(samples.global);
+            }
+        }
+        self
+    }
+
+    pub fn report(&self) -> AggregateReport {
+        AggregateReport {
+            by_name: self
+                .by_name
+                .iter()
+                .map(|(name, samples)| (name.clone(), samples.stats()))
+                .collect(),
+            by_path: self
+                .by_path
+                .iter()
+                .map(|(path, samples)| (path.clone(), samples.stats()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AggregateReport {
+    pub by_name: BTreeMap<String, Stats>,
+    pub by_path: BTreeMap<String, Stats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    pub calls: usize,
+    pub local: Distribution,
+    pub global: Distribution,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Distribution {
+    pub min: i32,
+    pub max: i32,
+    pub mean: f64,
+    pub median: f64,
+    pub p95: i32,
+}
+
+impl Distribution {
+    fn new(samples: &[i32]) -> Distribution {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let n = sorted.len();
+        if n == 0 {
+            return Distribution {
+                min: 0,
+                max: 0,
+                mean: 0.0,
+                median: 0.0,
+                p95: 0,
+            };
+        }
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] as f64 + sorted[n / 2] as f64) / 2.0
+        } else {
+            sorted[n / 2] as f64
+        };
+        // Nearest-rank percentile.
+        let p95 = sorted[(n * 95).div_ceil(100) - 1];
+        Distribution {
+            min: sorted[0],
+            max: sorted[n - 1],
+            mean: sorted.iter().map(|&s| s as f64).sum::<f64>() / n as f64,
+            median,
+            p95,
+        }
+    }
+}
+
+impl AggregateReport {
+    /// Prints the per-name statistics as a table.
+    pub fn print(&self) {
+        println!(
+            "{:<40} {:>7} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "function", "calls", "local avg", "median", "p95", "global avg", "median", "p95"
+        );
+        for (name, stats) in &self.by_name {
+            println!(
+                "{:<40} {:>7} {:>10.1} {:>10.1} {:>10} {:>10.1} {:>10.1} {:>10}",
+                name,
+                stats.calls,
+                stats.local.mean,
+                stats.local.median,
+                stats.local.p95,
+                stats.global.mean,
+                stats.global.median,
+                stats.global.p95,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixtures::entries;
+
+    const INPUT: &[&str] = &[
+        "Program SOME_PROGRAM invoke [1]",
+        "Program log: fn_one {{",
+        "Program consumption: 199000 units remaining",
+        "Program consumption: 198000 units remaining",
+        "Program log: }} // fn_one",
+        "Program log: fn_two {{",
+        "Program consumption: 197000 units remaining",
+        "Program log: fn_one {{",
+        "Program consumption: 196000 units remaining",
+        "Program consumption: 195000 units remaining",
+        "Program log: }} // fn_one",
+        "Program consumption: 194000 units remaining",
+        "Program log: }} // fn_two",
+        "Program SOME_PROGRAM consumed 7000 of 200000 compute units",
+        "Program SOME_PROGRAM success",
+    ];
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_aggregate() {
+        let mut first = Aggregate::new();
+        let mut second = Aggregate::new();
+        for entry in entries(INPUT) {
+            first.add_entry(&entry);
+        }
+        for entry in entries(&INPUT[1..5]) {
+            second.add_entry(&entry);
+        }
+        let report = first.merge(second).report();
+
+        assert_eq!(report.by_name["fn_one"].calls, 3);
+        assert_eq!(report.by_name["fn_two"].calls, 1);
+        assert_eq!(report.by_path["fn_one"].calls, 1);
+        assert_eq!(report.by_path["SOME_PROGRAM/fn_one"].calls, 1);
+        assert_eq!(report.by_path["SOME_PROGRAM/fn_two/fn_one"].calls, 1);
+        assert_eq!(
+            report.by_name["fn_one"].local,
+            Distribution {
+                min: 1000,
+                max: 1000,
+                mean: 1000.0,
+                median: 1000.0,
+                p95: 1000,
+            }
+        );
+    }
+
+    #[test]
+    fn test_distribution() {
+        let distribution = Distribution::new(&(1..=20).rev().collect::<Vec<_>>());
+        assert_eq!(distribution.min, 1);
+        assert_eq!(distribution.max, 20);
+        assert_eq!(distribution.mean, 10.5);
+        assert_eq!(distribution.median, 10.5);
+        assert_eq!(distribution.p95, 19);
+    }
+}
@@ -0,0 +1,347 @@
+extern crate serde;
+extern crate serde_json;
+extern crate sol_dev_utils;
+extern crate toml;
+
+use std::path::Path;
+
+use self::serde::{Deserialize, Serialize};
+
+use super::{
+    consumption::Consumer,
+    error::Error,
+    log::{Entry, InnerLog},
+};
+
+pub use self::sol_dev_utils::{PROBE, PROBE_INNER};
+
+/// Calibrates every log from its own probe.
+pub const AUTO: &str = "auto";
+
+/// What logging one frame costs, see `Consumer`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogCost {
+    /// The cost of the logs around the frame, as seen by its caller.
+    pub caller: i32,
+    /// The part of `caller` that shows up between the frame's own counters.
+    pub inner: i32,
+}
+
+/// The log costs of every kind of frame on one runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Calibration {
+    pub function: LogCost,
+    /// Functions logged by `log = "binary"`.
+    #[serde(default = "default_binary")]
+    pub binary: LogCost,
+    /// The runtime logs invokes for free.
+    #[serde(default)]
+    pub invoke: LogCost,
+    /// The fixed cost of a cross-program invocation, charged to the caller before
+    /// the callee starts consuming.
+    #[serde(default = "default_cpi")]
+    pub cpi: i32,
+    /// Derive `function` from the probe in each log instead, where there is one.
+    #[serde(default)]
+    pub auto: bool,
+}
+
+/// Named calibrations, by runtime version.
+pub const PRESETS: &[(&str, Calibration)] = &[
+    // Measured on 2024-09-01, see `compute_fn`: 2 x msg! + 2 x sol_log_compute_units
+    // for the caller, of which one sol_log_compute_units is logged inside the frame.
+    (
+        "1.18",
+        Calibration {
+            function: LogCost {
+                caller: 409,
+                inner: 101,
+            },
+            // Not measured, so binary frames are left uncorrected. Their costs come
+            // from a probe with `auto`, or from a file.
+            binary: LogCost {
+                caller: 0,
+                inner: 0,
+            },
+            invoke: LogCost {
+                caller: 0,
+                inner: 0,
+            },
+            // `invoke_units` of the default compute budget.
+            cpi: 1000,
+            auto: false,
+        },
+    ),
+    // Raw measurements, without correcting for the cost of logging.
+    (
+        "none",
+        Calibration {
+            function: LogCost {
+                caller: 0,
+                inner: 0,
+            },
+            binary: LogCost {
+                caller: 0,
+                inner: 0,
+            },
+            invoke: LogCost {
+                caller: 0,
+                inner: 0,
+            },
+            cpi: 0,
+            auto: false,
+        },
+    ),
+];
+
+fn default_binary() -> LogCost {
+    Calibration::default().binary
+}
+
+fn default_cpi() -> i32 {
+    Calibration::default().cpi
+}
+
+/// The preset used when none is given.
+pub const DEFAULT_PRESET: &str = "1.18";
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration::preset(DEFAULT_PRESET).unwrap()
+    }
+}
+
+impl Calibration {
+    pub fn preset(name: &str) -> Option<Calibration> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, calibration)| *calibration)
+    }
+
+    /// Reads `calibration`, the name of a preset, `auto` or a TOML or JSON file with
+    /// the log costs of functions and, optionally, invokes.
+    pub fn load(calibration: &str) -> Result<Calibration, Error> {
+        if calibration == AUTO {
+            return Ok(Calibration {
+                auto: true,
+                ..Calibration::default()
+            });
+        }
+        if let Some(preset) = Calibration::preset(calibration) {
+            return Ok(preset);
+        }
+        let path = Path::new(calibration);
+        if !path.is_file() {
+            let presets = PRESETS
+                .iter()
+                .map(|(preset, _)| *preset)
+                .collect::<Vec<_>>();
+            return Err(Error::Calibration(format!(
+                "{} is neither a preset ({}), {} nor a file",
+                calibration,
+                presets.join(", "),
+                AUTO
+            )));
+        }
+        let contents = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&contents).map_err(|e| e.to_string()),
+            _ => serde_json::from_str(&contents).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| Error::Calibration(format!("{}: {}", calibration, e)))
+    }
+
+    /// What invoking a program at `depth` costs its caller. Top level invokes are
+    /// paid for by the transaction.
+    pub fn cpi_cost(&self, depth: u32) -> i32 {
+        match depth {
+            0 | 1 => 0,
+            _ => self.cpi,
+        }
+    }
+
+    /// Sets the log cost of every frame in `entry`. Probes that calibrated it are
+    /// hidden afterwards.
+    pub fn apply(&self, entry: &mut Entry) {
+        let probed = Some(self)
+            .filter(|calibration| calibration.auto)
+            .and_then(|_| self.probe(entry.inner_logs()));
+        probed
+            .as_ref()
+            .unwrap_or(self)
+            .apply_all(entry.inner_logs_mut());
+        if probed.is_some() {
+            entry
+                .inner_logs_mut()
+                .iter_mut()
+                .for_each(Calibration::hide);
+        }
+    }
+
+    /// Moves the probes under `inner_log` into the `pruned` of their parents, where
+    /// they still count towards the consumption.
+    fn hide(inner_log: &mut InnerLog) {
+        let (children, pruned) = match inner_log {
+            InnerLog::Function(function) => (&mut function.children, &mut function.pruned),
+            InnerLog::Invoke(invoke) => (&mut invoke.children, &mut invoke.pruned),
+            _ => return,
+        };
+        let (probes, kept) = std::mem::take(children)
+            .into_iter()
+            .partition(|child| matches!(child, InnerLog::Function(probe) if probe.id == PROBE));
+        *children = kept;
+        pruned.extend(probes);
+        children.iter_mut().for_each(Calibration::hide);
+    }
+
+    /// This calibration with the log cost of the flavor of the first probe in
+    /// `inner_logs` replaced by what the probe measured.
+    ///
+    /// The inner probe is empty, so all it measures is the log between its counters.
+    /// The outer probe measures everything its child costs the caller, plus that
+    /// same log.
+    pub fn probe(&self, inner_logs: &[InnerLog]) -> Option<Calibration> {
+        inner_logs.iter().find_map(|inner_log| match inner_log {
+            InnerLog::Function(outer) if outer.id == PROBE => {
+                let inner = outer.children.iter().find_map(|child| match child {
+                    InnerLog::Function(inner) if inner.id == PROBE_INNER => Some(inner),
+                    _ => None,
+                })?;
+                let cost = LogCost {
+                    caller: outer.naive_global() - inner.naive_global(),
+                    inner: inner.naive_global(),
+                };
+                if cost.inner <= 0 || cost.caller <= cost.inner {
+                    tracing::warn!("Ignoring probe with implausible costs {:?}", cost);
+                    None
+                } else if outer.binary {
+                    Some(Calibration {
+                        binary: cost,
+                        ..*self
+                    })
+                } else {
+                    Some(Calibration {
+                        function: cost,
+                        ..*self
+                    })
+                }
+            }
+            _ => self.probe(inner_log.inner_logs()),
+        })
+    }
+
+    fn apply_all(&self, inner_logs: &mut [InnerLog]) {
+        for inner_log in inner_logs {
+            match inner_log {
+                InnerLog::Function(function) if function.binary => function.log_cost = self.binary,
+                InnerLog::Function(function) => function.log_cost = self.function,
+                InnerLog::Invoke(invoke) => {
+                    invoke.log_cost = self.invoke;
+                    invoke.cpi_cost = self.cpi_cost(invoke.depth);
+                }
+                _ => {}
+            }
+            self.apply_all(inner_log.inner_logs_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{
+        consumption::Consumer,
+        fixtures::{self, NESTED},
+    };
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_apply() {
+        let mut entry = fixtures::entry(NESTED);
+        let fn_one = |entry: &Entry| entry.inner_logs()[0].inner_logs()[0].local_ex_log();
+        assert_eq!(fn_one(&entry), 3000 - 2 * (409 - 101));
+
+        Calibration::preset("none").unwrap().apply(&mut entry);
+        assert_eq!(fn_one(&entry), 3000);
+
+        let calibration = Calibration {
+            function: LogCost {
+                caller: 300,
+                inner: 100,
+            },
+            binary: Calibration::default().binary,
+            invoke: LogCost::default(),
+            cpi: 1000,
+            auto: false,
+        };
+        calibration.apply(&mut entry);
+        assert_eq!(fn_one(&entry), 3000 - 2 * 200);
+        assert_eq!(
+            toml::from_str::<Calibration>("[function]\ncaller = 300\ninner = 100\n").unwrap(),
+            calibration
+        );
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_probe() {
+        let lines = [
+            "Program SOME_PROGRAM invoke [1]",
+            "Program log: fn_one {{",
+            "Program consumption: 199000 units remaining",
+            "Program log: sol_dev_probe {{",
+            "Program consumption: 198800 units remaining",
+            "Program log: sol_dev_probe_inner {{",
+            "Program consumption: 198500 units remaining",
+            "Program consumption: 198380 units remaining",
+            "Program log: }} // sol_dev_probe_inner",
+            "Program consumption: 198300 units remaining",
+            "Program log: }} // sol_dev_probe",
+            "Program consumption: 196000 units remaining",
+            "Program log: }} // fn_one",
+            "Program SOME_PROGRAM consumed 4000 of 200000 compute units",
+            "Program SOME_PROGRAM success",
+        ];
+        let log_cost = |entry: &Entry| match &entry.inner_logs()[0].inner_logs()[0] {
+            InnerLog::Function(function) => function.log_cost,
+            other => panic!("{:?}", other),
+        };
+        let mut entry = fixtures::entry(&lines);
+        assert_eq!(
+            Calibration::default()
+                .probe(entry.inner_logs())
+                .map(|calibration| calibration.function),
+            Some(LogCost {
+                caller: 500 - 120,
+                inner: 120
+            })
+        );
+
+        Calibration::load(AUTO).unwrap().apply(&mut entry);
+        assert_eq!(
+            log_cost(&entry),
+            LogCost {
+                caller: 380,
+                inner: 120
+            }
+        );
+        let fn_one = &entry.inner_logs()[0].inner_logs()[0];
+        assert!(fn_one.inner_logs().is_empty());
+        assert_eq!(fn_one.local_ex_log(), 3000 - 500 - (380 - 120));
+
+        let mut entry = fixtures::entry(NESTED);
+        Calibration::load(AUTO).unwrap().apply(&mut entry);
+        assert_eq!(log_cost(&entry), Calibration::default().function);
+    }
+
+    #[test]
+    fn test_load() {
+        assert_eq!(Calibration::load("1.18").unwrap(), Calibration::default());
+        assert!(Calibration::load(AUTO).unwrap().auto);
+        assert!(matches!(
+            Calibration::load("0.1"),
+            Err(Error::Calibration(_))
+        ));
+    }
+}
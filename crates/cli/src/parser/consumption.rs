@@ -3,7 +3,8 @@ use self::serde::{Deserialize, Serialize};
 
 use super::{function::Function, invoke::Invoke, log::InnerLog};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Report {
     pub naive_local: i32,
     pub naive_global: i32,
@@ -64,7 +65,9 @@ pub trait Consumer: std::fmt::Debug {
     fn compute_end(&self) -> i32;
     fn number_of_children(&self) -> i32;
     fn children(&self) -> Vec<&dyn Consumer>;
+    /// Taken from the `Calibration` of the runtime the log was produced on.
     fn log_cost_inner(&self) -> i32;
+    /// Taken from the `Calibration` of the runtime the log was produced on.
     fn log_cost_caller(&self) -> i32;
 
     /// The cost of logging this log, excluding the internal cost.
@@ -129,25 +132,36 @@ impl Consumer for Function<'_> {
     }
 
     fn number_of_children(&self) -> i32 {
-        self.children.iter().map(|c| c.number_of_children()).sum()
+        self.children
+            .iter()
+            .chain(&self.pruned)
+            .map(|c| c.number_of_children())
+            .sum()
     }
 
     fn children(&self) -> Vec<&dyn Consumer> {
-        self.children.iter().map(|c| c as &dyn Consumer).collect()
+        self.children
+            .iter()
+            .chain(&self.pruned)
+            .map(|c| c as &dyn Consumer)
+            .collect()
     }
 
     fn log_cost_caller(&self) -> i32 {
-        Self::LOG_COST_CALLER
+        self.log_cost.caller
     }
 
     fn log_cost_inner(&self) -> i32 {
-        Self::LOG_COST_INNER
+        self.log_cost.inner
     }
 }
 
+/// An invoke only reports what it consumed, so it is measured from 0 up.
+/// A CPI also costs its caller a fixed amount before the callee starts, which
+/// belongs to the invoke rather than to the function that does it.
 impl Consumer for Invoke<'_> {
     fn compute_start(&self) -> i32 {
-        self.consumption as i32
+        self.consumption as i32 + self.cpi_cost
     }
 
     fn compute_end(&self) -> i32 {
@@ -155,17 +169,25 @@ impl Consumer for Invoke<'_> {
     }
 
     fn number_of_children(&self) -> i32 {
-        self.children.iter().map(|c| c.number_of_children()).sum()
+        self.children
+            .iter()
+            .chain(&self.pruned)
+            .map(|c| c.number_of_children())
+            .sum()
     }
     fn children(&self) -> Vec<&dyn Consumer> {
-        self.children.iter().map(|c| c as &dyn Consumer).collect()
+        self.children
+            .iter()
+            .chain(&self.pruned)
+            .map(|c| c as &dyn Consumer)
+            .collect()
     }
     fn log_cost_caller(&self) -> i32 {
-        Self::LOG_COST_CALLER
+        self.log_cost.caller
     }
 
     fn log_cost_inner(&self) -> i32 {
-        Self::LOG_COST_INNER
+        self.log_cost.inner
     }
 }
 
@@ -174,7 +196,7 @@ impl Consumer for InnerLog<'_> {
         match self {
             InnerLog::Function(c) => c.compute_start(),
             InnerLog::Invoke(i) => i.compute_start(),
-            InnerLog::Unknown(_) => 0,
+            _ => 0,
         }
     }
 
@@ -182,7 +204,7 @@ impl Consumer for InnerLog<'_> {
         match self {
             InnerLog::Function(c) => c.compute_end(),
             InnerLog::Invoke(i) => i.compute_end(),
-            InnerLog::Unknown(_) => 0,
+            _ => 0,
         }
     }
 
@@ -190,7 +212,7 @@ impl Consumer for InnerLog<'_> {
         match self {
             InnerLog::Function(c) => c.number_of_children(),
             InnerLog::Invoke(i) => i.number_of_children(),
-            InnerLog::Unknown(_) => 0,
+            _ => 0,
         }
     }
 
@@ -198,7 +220,7 @@ impl Consumer for InnerLog<'_> {
         match self {
             InnerLog::Function(c) => c.children(),
             InnerLog::Invoke(i) => i.children(),
-            InnerLog::Unknown(_) => vec![],
+            _ => vec![],
         }
     }
 
@@ -206,7 +228,7 @@ impl Consumer for InnerLog<'_> {
         match self {
             InnerLog::Function(c) => c.log_cost_caller(),
             InnerLog::Invoke(i) => i.log_cost_caller(),
-            InnerLog::Unknown(_) => 0,
+            _ => 0,
         }
     }
 
@@ -214,14 +236,18 @@ impl Consumer for InnerLog<'_> {
         match self {
             InnerLog::Function(c) => c.log_cost_inner(),
             InnerLog::Invoke(i) => i.log_cost_inner(),
-            InnerLog::Unknown(_) => 0,
+            _ => 0,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::log::Log;
+    use crate::parser::{
+        calibration::Calibration,
+        fixtures,
+        log::{Entry, Log},
+    };
 
     use super::*;
     const INPUT: &[&str] = &[
@@ -328,4 +354,26 @@ mod tests {
     fn test_compute_consumption() {
         test_assert(INPUT);
     }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_cpi() {
+        let mut entry = fixtures::entry(INPUT);
+        let fn_four = |entry: &Entry| {
+            let fn_four = &entry.inner_logs()[0].inner_logs()[0].inner_logs()[1].inner_logs()[0];
+            let system = &fn_four.inner_logs()[0];
+            assert!(matches!(system, InnerLog::Invoke(_)));
+            (fn_four.local_ex_log(), system.global_ex_log())
+        };
+        let (local, system) = fn_four(&entry);
+        assert_eq!(system, 1000);
+
+        Calibration::preset("none").unwrap().apply(&mut entry);
+        let (local_none, system_none) = fn_four(&entry);
+        assert_eq!(system_none, 0);
+        // Log costs aside, the base cost moves from the caller to the invoke.
+        let log_costs = 2 * (409 - 101);
+        assert_eq!(local_none, local + 1000 + log_costs);
+        assert_eq!(local_none + system_none, local + system + log_costs);
+    }
 }
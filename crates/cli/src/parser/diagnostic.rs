@@ -0,0 +1,68 @@
+extern crate serde;
+
+use std::fmt;
+
+use self::serde::{Deserialize, Serialize};
+
+use super::log::InnerLog;
+
+/// Found instead of a line when the input ends early.
+pub const END_OF_INPUT: &str = "end of input";
+
+/// A place where the log did not have the expected shape.
+///
+/// The parser recovers by keeping the offending line as `Unknown`, and attaches
+/// the diagnostic to that node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    #[serde(flatten)]
+    pub position: Position,
+    pub expected: String,
+    pub found: String,
+    /// Call path of the open frames, e.g. `<program id>/process_instruction`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame: Option<String>,
+}
+
+/// Where in the input a diagnostic is, serialized as `"line": 3` or `"response": 2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Position {
+    /// 1-based number of the offending line of a log.
+    Line(usize),
+    /// 1-based number of the response in RPC input that holds no transaction.
+    Response(usize),
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Position::Line(line) => write!(f, "line {}", line),
+            Position::Response(response) => write!(f, "response {}", response),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, found `{}`",
+            self.position, self.expected, self.found
+        )?;
+        if let Some(frame) = &self.frame {
+            write!(f, " in {}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the diagnostics attached anywhere in `inner_logs`, in line order.
+pub fn collect<'b>(inner_logs: &'b [InnerLog], diagnostics: &mut Vec<&'b Diagnostic>) {
+    for inner_log in inner_logs {
+        if let InnerLog::Unknown(unknown) = inner_log {
+            diagnostics.extend(&unknown.diagnostic);
+        }
+        collect(inner_log.inner_logs(), diagnostics);
+    }
+}
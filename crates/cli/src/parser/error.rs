@@ -1,3 +1,5 @@
+use super::diagnostic::Diagnostic;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Invalid invocation {0}")]
@@ -10,4 +12,28 @@ pub enum Error {
 
     #[error("Serde error: {0}")]
     Serde(#[from] serde_json::Error),
+
+    #[error("Flamegraph error: {0}")]
+    Flamegraph(String),
+
+    #[error("Invalid IDL {0}")]
+    Idl(String),
+
+    #[error("Invalid program names {0}")]
+    Programs(String),
+
+    #[error("Invalid frame names {0}")]
+    Frames(String),
+
+    #[error("Invalid calibration {0}")]
+    Calibration(String),
+
+    #[error("Invalid filter {0}")]
+    Filter(String),
+
+    #[error("Malformed log at {0}")]
+    Malformed(Diagnostic),
+
+    #[error("{0} file(s) could not be parsed")]
+    Failed(usize),
 }
@@ -0,0 +1,314 @@
+extern crate regex;
+
+use self::regex::Regex;
+
+use super::{
+    consumption::Consumer,
+    error::Error,
+    log::{Entry, InnerLog},
+};
+
+/// One `/` separated part of an `--only` pattern.
+#[derive(Debug, Clone)]
+enum Segment {
+    /// `**`, any number of frames.
+    Any,
+    /// A frame name, where `*` matches any part of it.
+    Glob(Regex),
+}
+
+/// A path of frame names, e.g. `process_instruction/*/fn_seven` or `fn_seven/**`.
+/// Matches the end of a frame's path, or its whole path if it starts with `/`.
+#[derive(Debug, Clone)]
+struct Pattern {
+    anchored: bool,
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    fn new(pattern: &str) -> Result<Pattern, Error> {
+        let anchored = pattern.starts_with('/');
+        let segments = pattern
+            .trim_start_matches('/')
+            .split('/')
+            .map(|segment| match segment {
+                "" => Err(Error::Filter(format!("{}: empty frame name", pattern))),
+                "**" => Ok(Segment::Any),
+                glob => {
+                    let regex = format!("^{}$", regex::escape(glob).replace(r"\*", ".*"));
+                    Regex::new(&regex)
+                        .map(Segment::Glob)
+                        .map_err(|e| Error::Filter(format!("{}: {}", pattern, e)))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Pattern { anchored, segments })
+    }
+
+    fn matches(&self, path: &[Vec<String>]) -> bool {
+        if self.anchored {
+            return Pattern::matches_segments(&self.segments, path);
+        }
+        (0..path.len()).any(|start| Pattern::matches_segments(&self.segments, &path[start..]))
+    }
+
+    fn matches_segments(segments: &[Segment], path: &[Vec<String>]) -> bool {
+        match segments.split_first() {
+            None => path.is_empty(),
+            Some((Segment::Any, segments)) => {
+                (0..=path.len()).any(|skip| Pattern::matches_segments(segments, &path[skip..]))
+            }
+            Some((Segment::Glob(glob), segments)) => match path.split_first() {
+                Some((names, path)) => {
+                    names.iter().any(|name| glob.is_match(name))
+                        && Pattern::matches_segments(segments, path)
+                }
+                None => false,
+            },
+        }
+    }
+}
+
+/// Selects the part of every entry to output.
+///
+/// Frames selected with `--only` or `--program` are output on their own, with
+/// everything under them. Frames hidden with `--depth`, `--min-cu` or `--program !<id>`
+/// are left out of the output but still count towards the consumption of their parent.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    only: Vec<Pattern>,
+    programs: Vec<String>,
+    excluded_programs: Vec<String>,
+    min_cu: Option<i32>,
+    depth: Option<usize>,
+}
+
+impl Filter {
+    pub fn new(
+        only: &[String],
+        programs: &[String],
+        min_cu: Option<i32>,
+        depth: Option<usize>,
+    ) -> Result<Filter, Error> {
+        let (excluded_programs, programs) = programs
+            .iter()
+            .partition::<Vec<_>, _>(|program| program.starts_with('!'));
+        Ok(Filter {
+            only: only
+                .iter()
+                .map(|pattern| Pattern::new(pattern))
+                .collect::<Result<_, _>>()?,
+            programs: programs.into_iter().cloned().collect(),
+            excluded_programs: excluded_programs
+                .into_iter()
+                .map(|program| program[1..].to_string())
+                .collect(),
+            min_cu,
+            depth,
+        })
+    }
+
+    /// The entries to output in place of `entry`, none if nothing in it is selected.
+    pub fn apply<'a>(&self, entry: Entry<'a>) -> Vec<Entry<'a>> {
+        let mut entries = if self.only.is_empty() && self.programs.is_empty() {
+            vec![entry]
+        } else {
+            let inner_logs = match entry {
+                Entry::InnerLog(inner_log) => vec![inner_log],
+                Entry::Transaction(transaction) => transaction.log.inner_logs,
+            };
+            let mut selected = vec![];
+            for inner_log in inner_logs {
+                self.select(inner_log, &mut vec![], &mut selected);
+            }
+            selected.into_iter().map(Entry::InnerLog).collect()
+        };
+        entries.retain_mut(|entry| match entry {
+            Entry::InnerLog(inner_log) => !self.hides(inner_log, 1),
+            Entry::Transaction(transaction) => {
+                transaction
+                    .log
+                    .inner_logs
+                    .retain(|inner_log| !self.hides(inner_log, 1));
+                true
+            }
+        });
+        for entry in &mut entries {
+            for inner_log in entry.inner_logs_mut() {
+                self.prune(inner_log, 1);
+            }
+        }
+        entries
+    }
+
+    /// Names `--only` and `--program` match a frame by.
+    fn names(inner_log: &InnerLog) -> Vec<String> {
+        match inner_log {
+            InnerLog::Function(function) => vec![function.id.to_string()],
+            InnerLog::Invoke(invoke) => {
+                let mut names = vec![invoke.id.to_string(), invoke.label()];
+                names.extend(invoke.name.clone());
+                names
+            }
+            _ => vec![],
+        }
+    }
+
+    fn is_program(inner_log: &InnerLog, programs: &[String]) -> bool {
+        matches!(inner_log, InnerLog::Invoke(_))
+            && Filter::names(inner_log)
+                .iter()
+                .any(|name| programs.contains(name))
+    }
+
+    /// Moves the frames in `inner_log` that are selected into `selected`.
+    fn select<'a>(
+        &self,
+        inner_log: InnerLog<'a>,
+        path: &mut Vec<Vec<String>>,
+        selected: &mut Vec<InnerLog<'a>>,
+    ) {
+        let names = Filter::names(&inner_log);
+        if names.is_empty() {
+            return;
+        }
+        path.push(names);
+        if self.only.iter().any(|pattern| pattern.matches(path))
+            || Filter::is_program(&inner_log, &self.programs)
+        {
+            selected.push(inner_log);
+        } else {
+            let children = match inner_log {
+                InnerLog::Function(function) => function.children,
+                InnerLog::Invoke(invoke) => invoke.children,
+                _ => vec![],
+            };
+            for child in children {
+                self.select(child, path, selected);
+            }
+        }
+        path.pop();
+    }
+
+    fn hides(&self, inner_log: &InnerLog, depth: usize) -> bool {
+        let is_frame = matches!(inner_log, InnerLog::Function(_) | InnerLog::Invoke(_));
+        self.depth.is_some_and(|max| depth > max)
+            || self
+                .min_cu
+                .is_some_and(|min| is_frame && inner_log.global_ex_log() < min)
+            || Filter::is_program(inner_log, &self.excluded_programs)
+    }
+
+    /// Moves the children of `inner_log` that are hidden into its `pruned`.
+    fn prune(&self, inner_log: &mut InnerLog, depth: usize) {
+        let (children, pruned) = match inner_log {
+            InnerLog::Function(function) => (&mut function.children, &mut function.pruned),
+            InnerLog::Invoke(invoke) => (&mut invoke.children, &mut invoke.pruned),
+            _ => return,
+        };
+        let (hidden, kept) = std::mem::take(children)
+            .into_iter()
+            .partition(|child| self.hides(child, depth + 1));
+        *children = kept;
+        pruned.extend(hidden);
+        for child in children.iter_mut() {
+            self.prune(child, depth + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{fixtures::entry, programs::Programs};
+
+    const INPUT: &[&str] = &[
+        "Program SOME_PROGRAM invoke [1]",
+        "Program log: fn_one {{",
+        "Program consumption: 199000 units remaining",
+        "Program log: fn_two {{",
+        "Program consumption: 198000 units remaining",
+        "Program 11111111111111111111111111111111 invoke [2]",
+        "Program 11111111111111111111111111111111 success",
+        "Program consumption: 197000 units remaining",
+        "Program log: }} // fn_two",
+        "Program log: fn_seven {{",
+        "Program consumption: 196000 units remaining",
+        "Program log: fn_eight {{",
+        "Program consumption: 195990 units remaining",
+        "Program consumption: 195900 units remaining",
+        "Program log: }} // fn_eight",
+        "Program consumption: 190000 units remaining",
+        "Program log: }} // fn_seven",
+        "Program consumption: 189000 units remaining",
+        "Program log: }} // fn_one",
+        "Program SOME_PROGRAM consumed 12000 of 200000 compute units",
+        "Program SOME_PROGRAM success",
+    ];
+
+    fn filtered(filter: &Filter) -> Vec<Entry<'static>> {
+        let mut entry = entry(INPUT);
+        Programs::new().label_entry(&mut entry);
+        filter.apply(entry)
+    }
+
+    fn ids(inner_logs: &[InnerLog]) -> Vec<String> {
+        inner_logs
+            .iter()
+            .map(|inner_log| inner_log.id().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_only() {
+        let only = |pattern: &str| {
+            let filter = Filter::new(&[pattern.to_string()], &[], None, None).unwrap();
+            filtered(&filter)
+                .iter()
+                .flat_map(|entry| ids(entry.inner_logs()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(only("fn_seven/**"), vec!["fn_seven"]);
+        assert_eq!(only("fn_*"), vec!["fn_one"]);
+        assert_eq!(only("fn_one/*"), vec!["fn_two", "fn_seven"]);
+        assert_eq!(only("/fn_one"), Vec::<String>::new());
+        assert_eq!(only("/SOME_PROGRAM/**/fn_eight"), vec!["fn_eight"]);
+        assert!(Filter::new(&["fn_one//".to_string()], &[], None, None).is_err());
+
+        let filter = Filter::new(&[], &["System".to_string()], None, None).unwrap();
+        assert_eq!(
+            ids(filtered(&filter)[0].inner_logs()),
+            vec!["11111111111111111111111111111111"]
+        );
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_prune() {
+        let fn_one = |entries: &[Entry]| {
+            let fn_one = &entries[0].inner_logs()[0].inner_logs()[0];
+            (ids(fn_one.inner_logs()), fn_one.local_ex_log())
+        };
+        let grandchildren = |entries: &[Entry]| {
+            let fn_one = &entries[0].inner_logs()[0].inner_logs()[0];
+            ids(fn_one.inner_logs()[0].inner_logs())
+        };
+        let (children, local) = fn_one(&filtered(&Filter::default()));
+        assert_eq!(children, vec!["fn_two", "fn_seven"]);
+
+        // Hidden frames still count towards their parent.
+        let filter = Filter::new(&[], &[], Some(2000), None).unwrap();
+        let entries = filtered(&filter);
+        assert_eq!(fn_one(&entries), (vec!["fn_seven".to_string()], local));
+        assert_eq!(grandchildren(&entries), Vec::<String>::new());
+
+        let filter = Filter::new(&[], &[], None, Some(2)).unwrap();
+        assert_eq!(fn_one(&filtered(&filter)), (vec![], local));
+
+        let filter = Filter::new(&[], &["!System".to_string()], None, None).unwrap();
+        let entries = filtered(&filter);
+        assert_eq!(grandchildren(&entries), Vec::<String>::new());
+        assert_eq!(fn_one(&entries).1, local);
+    }
+}
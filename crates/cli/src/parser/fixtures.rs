@@ -0,0 +1,46 @@
+//! Logs shared by the parser tests.
+use super::log::{Entry, Log};
+
+/// `SOME_PROGRAM` calling `fn_one` once.
+pub const CALL: &[&str] = &[
+    "Program SOME_PROGRAM invoke [1]",
+    "Program log: fn_one {{",
+    "Program consumption: 199004 units remaining",
+    "Program consumption: 198660 units remaining",
+    "Program log: }} // fn_one",
+    "Program SOME_PROGRAM consumed 7218 of 200000 compute units",
+    "Program SOME_PROGRAM success",
+];
+
+/// `SOME_PROGRAM` calling `fn_one`, which calls `fn_two` twice.
+pub const NESTED: &[&str] = &[
+    "Program SOME_PROGRAM invoke [1]",
+    "Program log: fn_one {{",
+    "Program consumption: 199000 units remaining",
+    "Program log: fn_two {{",
+    "Program consumption: 198000 units remaining",
+    "Program consumption: 197000 units remaining",
+    "Program log: }} // fn_two",
+    "Program log: fn_two {{",
+    "Program consumption: 196000 units remaining",
+    "Program consumption: 193000 units remaining",
+    "Program log: }} // fn_two",
+    "Program consumption: 192000 units remaining",
+    "Program log: }} // fn_one",
+    "Program SOME_PROGRAM consumed 9000 of 200000 compute units",
+    "Program SOME_PROGRAM success",
+];
+
+/// The top-level logs of `lines`, as entries.
+pub fn entries<'a>(lines: &'a [&'a str]) -> Vec<Entry<'a>> {
+    Log::from_slice(lines)
+        .inner_logs
+        .into_iter()
+        .map(Entry::InnerLog)
+        .collect()
+}
+
+/// The first top-level log of `lines`, as an entry.
+pub fn entry<'a>(lines: &'a [&'a str]) -> Entry<'a> {
+    entries(lines).remove(0)
+}
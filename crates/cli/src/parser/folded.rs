@@ -0,0 +1,129 @@
+extern crate inferno;
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use self::inferno::flamegraph;
+
+use super::{
+    consumption::Consumer,
+    error::Error,
+    log::{Entry, InnerLog},
+};
+
+/// The measurement used as the sample count of a frame.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weight {
+    /// Cost of the frame excluding its children and the cost of logging.
+    Local,
+    /// Cost of the frame excluding its children, including the cost of logging.
+    NaiveLocal,
+}
+
+impl Weight {
+    fn of(self, consumer: &dyn Consumer) -> i32 {
+        match self {
+            Weight::Local => consumer.local_ex_log(),
+            Weight::NaiveLocal => consumer.naive_local(),
+        }
+    }
+}
+
+/// Call stacks in Brendan Gregg's folded format, e.g.
+/// `process_instruction;fn_seven;some_swap;<program id> 32070`.
+///
+/// Identical stacks are merged, so repeated calls add up.
+#[derive(Debug)]
+pub struct Folded {
+    weight: Weight,
+    stacks: BTreeMap<String, u64>,
+}
+
+impl Folded {
+    pub fn new(weight: Weight) -> Folded {
+        Folded {
+            weight,
+            stacks: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_entry(&mut self, entry: &Entry) {
+        for inner_log in entry.inner_logs() {
+            self.add(inner_log, "");
+        }
+    }
+
+    fn add(&mut self, inner_log: &InnerLog, parent: &str) {
+        let name = match inner_log.id() {
+            Some(name) => name,
+            None => return,
+        };
+        let stack = if parent.is_empty() {
+            name.to_string()
+        } else {
+            format!("{};{}", parent, name)
+        };
+        // Measurement noise can push small frames below zero.
+        let weight = self.weight.of(inner_log).max(0) as u64;
+        if weight > 0 {
+            *self.stacks.entry(stack.clone()).or_default() += weight;
+        }
+        for child in inner_log.inner_logs() {
+            self.add(child, &stack);
+        }
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.stacks
+            .iter()
+            .map(|(stack, weight)| format!("{} {}", stack, weight))
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        for line in self.lines() {
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Renders the stacks as an SVG flamegraph.
+    pub fn write_svg<W: Write>(&self, writer: W, title: &str) -> Result<(), Error> {
+        let mut options = flamegraph::Options::default();
+        options.title = title.to_string();
+        options.count_name = "CU".to_string();
+        let lines = self.lines().collect::<Vec<_>>();
+        flamegraph::from_lines(&mut options, lines.iter().map(String::as_str), writer)
+            .map_err(|e| Error::Flamegraph(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixtures::{entries, NESTED};
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_folded() {
+        let mut folded = Folded::new(Weight::NaiveLocal);
+        for entry in entries(NESTED) {
+            folded.add_entry(&entry);
+        }
+        let lines = folded.lines().collect::<Vec<_>>();
+        tracing::debug!("{:?}", lines);
+        assert_eq!(
+            lines,
+            vec![
+                "SOME_PROGRAM 2000",
+                "SOME_PROGRAM;fn_one 3000",
+                "SOME_PROGRAM;fn_one;fn_two 4000",
+            ]
+        );
+
+        let mut svg = vec![];
+        folded.write_svg(&mut svg, "test").unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.contains("<svg"));
+        assert!(svg.contains("fn_two"));
+    }
+}
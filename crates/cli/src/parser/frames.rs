@@ -0,0 +1,323 @@
+extern crate serde_json;
+extern crate sol_dev_utils;
+extern crate toml;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::Path;
+
+use self::sol_dev_utils::{frame_id, FRAME_TAG};
+
+use super::{
+    calibration::{Calibration, PROBE, PROBE_INNER},
+    error::Error,
+    function::Function,
+    log::{Entry, InnerLog},
+};
+
+/// The end of a frame logged by `#[compute_fn(log = "binary")]`:
+/// `Program log: <tag>, <id>, <units remaining at the start>, <at the end>, 0x0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exit {
+    pub id: u64,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl Exit {
+    pub fn decode(line: &str) -> Option<Exit> {
+        let mut fields = line
+            .strip_prefix("Program log: ")?
+            .split(", ")
+            .map(|field| u64::from_str_radix(field.strip_prefix("0x")?, 16).ok());
+        let [tag, id, start, end, _] = [(); 5].map(|_| fields.next().flatten());
+        if tag? != FRAME_TAG || fields.next().is_some() {
+            return None;
+        }
+        Some(Exit {
+            id: id?,
+            start: u32::try_from(start?).ok()?,
+            end: u32::try_from(end?).ok()?,
+        })
+    }
+
+    /// The function that ended, named by its id, e.g. `0x01dabe85903dc59a`, until
+    /// `Frames` labels it.
+    pub fn function<'a>(&self) -> Function<'a> {
+        let mut function = Function::new(Frames::name_of(self.id));
+        function.consumption_start = self.start;
+        function.consumption_end = self.end;
+        function.log_cost = Calibration::default().binary;
+        function.binary = true;
+        function
+    }
+
+    /// Moves the last of `siblings` that ran inside the frame into it, and appends it.
+    ///
+    /// The start of a frame is not logged, so what ran inside is told by the units
+    /// remaining: functions that started and invokes that were given their budget after
+    /// the frame started. Invokes of builtins log no budget, so they are told by their
+    /// position instead: those after the last sibling that started before the frame ran
+    /// inside it. Lines without units in between come along, those in front of the
+    /// first function or invoke inside the frame stay with its parent.
+    pub fn nest<'a>(&self, siblings: &mut Vec<InnerLog<'a>>) {
+        let mut split = siblings.len();
+        for (index, sibling) in siblings.iter().enumerate().rev() {
+            let started = match sibling {
+                InnerLog::Function(function) => Some(function.consumption_start),
+                InnerLog::Invoke(invoke) if invoke.budget > 0 => Some(invoke.budget),
+                InnerLog::Invoke(_) => None,
+                _ => continue,
+            };
+            if started.is_some_and(|started| started > self.start) {
+                break;
+            }
+            split = index;
+        }
+        let mut function = self.function();
+        function.children = siblings.split_off(split);
+        siblings.push(InnerLog::Function(function));
+    }
+}
+
+/// Names of the frames in binary logs, by id.
+///
+/// Starts out with the probe frames. More are added from the files `compute_fn` writes
+/// to `$SOL_DEV_FRAMES/<crate>/<id>`, each holding a name, or from TOML or JSON maps
+/// of ids to names.
+#[derive(Debug, Clone)]
+pub struct Frames {
+    names: HashMap<u64, String>,
+}
+
+impl Frames {
+    pub fn new() -> Frames {
+        Frames {
+            names: [PROBE, PROBE_INNER]
+                .iter()
+                .map(|name| (frame_id(name), name.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Loads every name in `paths`, directories as written to `$SOL_DEV_FRAMES`, or
+    /// maps.
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Frames, Error> {
+        let mut frames = Frames::new();
+        for path in paths {
+            frames.load_path(path.as_ref())?;
+        }
+        Ok(frames)
+    }
+
+    fn load_path(&mut self, path: &Path) -> Result<(), Error> {
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                self.load_path(&entry?.path())?;
+            }
+            return Ok(());
+        }
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "toml" || extension == "json")
+        {
+            return self.load_file(path);
+        }
+        // Anything else, such as a name that is still being written, is not an id.
+        if let Some(id) = path
+            .file_name()
+            .and_then(|file| Frames::parse_id(file.to_str()?))
+        {
+            let name = std::fs::read_to_string(path)?;
+            self.names.insert(id, name);
+        }
+        Ok(())
+    }
+
+    fn load_file(&mut self, path: &Path) -> Result<(), Error> {
+        let contents = std::fs::read_to_string(path)?;
+        let loaded = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => {
+                toml::from_str::<HashMap<String, String>>(&contents).map_err(|e| e.to_string())
+            }
+            _ => serde_json::from_str::<HashMap<String, String>>(&contents)
+                .map_err(|e| e.to_string()),
+        }
+        .and_then(|names| self.add(names));
+        loaded.map_err(|e| Error::Frames(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Adds a mapping of ids, as hex, to names.
+    fn add(&mut self, names: HashMap<String, String>) -> Result<(), String> {
+        for (id, name) in names {
+            let parsed = Frames::parse_id(&id).ok_or_else(|| format!("{} is not a hex id", id))?;
+            self.names.insert(parsed, name);
+        }
+        Ok(())
+    }
+
+    /// The name `Exit::function` gives a function, as the files `compute_fn` writes are named.
+    fn name_of(id: u64) -> String {
+        format!("{:#018x}", id)
+    }
+
+    fn parse_id(id: &str) -> Option<u64> {
+        u64::from_str_radix(id.strip_prefix("0x")?, 16).ok()
+    }
+
+    pub fn name(&self, id: &str) -> Option<&str> {
+        self.names.get(&Frames::parse_id(id)?).map(String::as_str)
+    }
+
+    /// Names every function in `entry` that has a known id.
+    pub fn label_entry(&self, entry: &mut Entry) {
+        self.label_all(entry.inner_logs_mut());
+    }
+
+    fn label_all(&self, inner_logs: &mut [InnerLog]) {
+        for inner_log in inner_logs {
+            if let InnerLog::Function(function) = inner_log {
+                if let Some(name) = self.name(&function.id) {
+                    function.id = name.to_string().into();
+                }
+            }
+            self.label_all(inner_log.inner_logs_mut());
+        }
+    }
+}
+
+impl Default for Frames {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{calibration::LogCost, consumption::Consumer, log::Log};
+
+    /// `sol_log_64` as the runtime logs it.
+    fn log_64(id: &str, start: u64, end: u64) -> String {
+        format!(
+            "Program log: {:#x}, {:#x}, {:#x}, {:#x}, 0x0",
+            FRAME_TAG,
+            frame_id(id),
+            start,
+            end
+        )
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_decode() {
+        let lines = vec![
+            "Program SOME_PROGRAM invoke [1]".to_string(),
+            "Program log: before fn_one".to_string(),
+            log_64(PROBE_INNER, 198500, 198380),
+            log_64(PROBE, 198800, 198300),
+            "Program log: in fn_one".to_string(),
+            "Program 11111111111111111111111111111111 invoke [2]".to_string(),
+            "Program 11111111111111111111111111111111 consumed 150 of 197500 compute units"
+                .to_string(),
+            "Program 11111111111111111111111111111111 success".to_string(),
+            log_64("fn_two", 198000, 197000),
+            log_64("fn_one", 199000, 196000),
+            "Program ComputeBudget111111111111111111111111111111 invoke [2]".to_string(),
+            "Program ComputeBudget111111111111111111111111111111 success".to_string(),
+            log_64("fn_three", 195900, 195700),
+            "Program log: 0x1, 0x2, 0x3, 0x4, 0x5".to_string(),
+            "Program SOME_PROGRAM consumed 4000 of 200000 compute units".to_string(),
+            "Program SOME_PROGRAM success".to_string(),
+        ];
+        let mut entry = Entry::InnerLog(Log::from_lines(lines).inner_logs.remove(0));
+        assert!(entry.diagnostics().is_empty());
+
+        let ids = |inner_logs: &[InnerLog]| {
+            inner_logs
+                .iter()
+                .map(|inner_log| inner_log.id().map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+        let mut frames = Frames::new();
+        frames.label_entry(&mut entry);
+        let program = &entry.inner_logs()[0];
+        let fn_one = Frames::name_of(frame_id("fn_one"));
+        let fn_three = Frames::name_of(frame_id("fn_three"));
+        assert_eq!(
+            ids(program.inner_logs()),
+            vec![None, Some(fn_one), Some(fn_three), None]
+        );
+        // A builtin logs no budget, but ran after everything that started before the frame.
+        assert_eq!(
+            ids(program.inner_logs()[2].inner_logs()),
+            vec![Some(
+                "ComputeBudget111111111111111111111111111111".to_string()
+            )]
+        );
+        // The probe nests by its units remaining, the invoke by its budget.
+        assert_eq!(
+            ids(program.inner_logs()[1].inner_logs()),
+            vec![
+                Some(PROBE.to_string()),
+                None,
+                Some(Frames::name_of(frame_id("fn_two")))
+            ]
+        );
+        assert_eq!(
+            ids(program.inner_logs()[1].inner_logs()[2].inner_logs()),
+            vec![Some("11111111111111111111111111111111".to_string())]
+        );
+        assert_eq!(
+            Calibration::default()
+                .probe(entry.inner_logs())
+                .map(|calibration| calibration.binary),
+            Some(LogCost {
+                caller: 500 - 120,
+                inner: 120
+            })
+        );
+
+        frames
+            .add(HashMap::from([
+                (format!("{:#x}", frame_id("fn_one")), "fn_one".to_string()),
+                (format!("{:#x}", frame_id("fn_two")), "fn_two".to_string()),
+            ]))
+            .unwrap();
+        frames.label_entry(&mut entry);
+        let fn_one = &entry.inner_logs()[0].inner_logs()[1];
+        assert_eq!(fn_one.id(), Some("fn_one"));
+        assert_eq!(fn_one.naive_global(), 3000);
+        assert!(frames
+            .add(HashMap::from([(
+                "fn_one".to_string(),
+                "fn_one".to_string()
+            )]))
+            .is_err());
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_load() {
+        let dir = std::env::temp_dir().join(format!("sol-dev-frames-{}", std::process::id()));
+        let krate = dir.join("some_program");
+        std::fs::create_dir_all(&krate).unwrap();
+        let id = Frames::name_of(frame_id("fn_one"));
+        std::fs::write(krate.join(&id), "fn_one").unwrap();
+        std::fs::write(krate.join(format!(".{}.1", id)), "fn_").unwrap();
+        std::fs::write(
+            dir.join("names.toml"),
+            format!("\"{:#x}\" = \"fn_two\"\n", frame_id("fn_two")),
+        )
+        .unwrap();
+
+        let frames = Frames::load(&[&dir]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(frames.name(&id), Some("fn_one"));
+        assert_eq!(
+            frames.name(&Frames::name_of(frame_id("fn_two"))),
+            Some("fn_two")
+        );
+        assert_eq!(frames.names.len(), 4);
+    }
+}
@@ -1,89 +1,88 @@
 extern crate regex;
 extern crate serde;
 
+use std::borrow::Cow;
 use std::convert::TryFrom;
 
 use self::regex::Regex;
 use self::serde::{Deserialize, Serialize};
 
+use super::calibration::{Calibration, LogCost};
 use super::error::Error;
 use super::log::InnerLog;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Function<'a> {
-    pub id: &'a str,
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
     #[serde(skip)]
     pub consumption_start: u32,
     #[serde(skip)]
     pub consumption_end: u32,
+    #[serde(skip)]
+    pub log_cost: LogCost,
+    /// Set when the function was logged by `log = "binary"`, which costs differently.
+    #[serde(skip)]
+    pub binary: bool,
+    /// Set when the function never logged its end because its invoke ended first,
+    /// e.g. on a panic. The cost is then measured up to the last known counter.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub aborted: bool,
     pub children: Vec<InnerLog<'a>>,
+    /// Children hidden by a `Filter`, which still count towards the consumption.
+    #[serde(skip)]
+    pub pruned: Vec<InnerLog<'a>>,
 }
 
 lazy_static::lazy_static! {
-    static ref RE_START: Regex = Regex::new(r"Program log: (\w+) \{\{").unwrap();
+    // Names are `::` separated segments, each a word with optional generic arguments or
+    // a qualified type on its own, e.g. `crate::<Vault<T> as Named>::name`. Spaces are
+    // only read inside angle brackets, nested up to three deep, so that messages such
+    // as `Instruction: Swap {{` are not names. The line ends at `{{`, unlike events
+    // such as `SomeEvent {{ version: 0 }}`.
+    static ref RE_START: Regex = {
+        let mut generic = r"<[\w:&,' ]*>".to_string();
+        for _ in 1..3 {
+            generic = format!(r"<(?:[\w:&,' ]|{})*>", generic);
+        }
+        let segment = format!(r"(?:\w+(?:{0})?|{0})", generic);
+        Regex::new(&format!(r"Program log: ({0}(?:::{0})*) \{{\{{$", segment)).unwrap()
+    };
     static ref RE_CONSUMPTION: Regex = Regex::new(r"Program consumption: (\d+) units remaining").unwrap();
 }
 
 // A compute log starts with a line that contains "Program log: <fn_name> {"
 // and ends with al ine that contains "Program log: } // <fn_name>"
-impl Function<'_> {
-    // Log cost for the caller is 2 x msg! + 2 x sol_log_compute_units = 601
-    pub const LOG_COST_CALLER: i32 = 409;
-    // Log cost for the inner is 2 x sol_log_compute_units = 202
-    pub const LOG_COST_INNER: i32 = 101;
-    pub fn new(fn_name: &'_ str) -> Function<'_> {
+impl<'a> Function<'a> {
+    pub fn new(fn_name: impl Into<Cow<'a, str>>) -> Function<'a> {
         Function {
-            id: fn_name,
+            id: fn_name.into(),
             consumption_start: 0,
             consumption_end: 0,
+            log_cost: Calibration::default().function,
+            binary: false,
+            aborted: false,
             children: vec![],
+            pruned: vec![],
         }
     }
 
-    pub fn is_end(&self, lines: &[&str]) -> bool {
-        lines.len() >= 2 && lines[1].contains(self.id) && lines[1].contains("}} //")
+    pub fn is_end_line(&self, line: &str) -> bool {
+        line.split_once("}} // ")
+            .is_some_and(|(_, id)| id == self.id)
     }
 
-    pub fn consume_start_lines<'a>(
-        &mut self,
-        lines: &'a [&'a str],
-    ) -> Result<&'a [&'a str], Error> {
-        let captures = RE_CONSUMPTION
-            .captures(lines[0])
-            .ok_or(Error::Function(lines[0].to_string()))?;
-        self.consumption_start = captures.get(1).unwrap().as_str().parse().unwrap();
-        Ok(&lines[1..])
+    /// Whether `line` ends any function.
+    pub fn is_any_end_line(line: &str) -> bool {
+        line.contains("}} //")
     }
 
-    pub fn consume_end_lines<'a>(&mut self, lines: &'a [&'a str]) -> Result<&'a [&'a str], Error> {
+    // Looks like "Program consumption: <units> units remaining"
+    pub fn parse_consumption(line: &str) -> Result<u32, Error> {
         let captures = RE_CONSUMPTION
-            .captures(lines[0])
-            .ok_or(Error::Function(lines[0].to_string()))?;
-        self.consumption_end = captures.get(1).unwrap().as_str().parse().unwrap();
-        Ok(&lines[2..])
-    }
-
-    pub fn try_from_slice<'a>(
-        lines: &'a [&'a str],
-    ) -> Result<(Function<'a>, &'a [&'a str]), Error> {
-        if lines.len() < 2 {
-            return Err(Error::Function("Not enough lines".to_string()));
-        }
-        let mut compute = Function::try_from(lines[0])?;
-        let mut lines = compute.consume_start_lines(&lines[1..])?;
-
-        while !lines.is_empty() && !compute.is_end(lines) {
-            let (inner_log, remaining_lines) = InnerLog::from_slice(lines);
-            tracing::debug!("child {:?}", inner_log);
-            compute.children.push(inner_log);
-            lines = remaining_lines;
-        }
-        if lines.is_empty() {
-            return Err(Error::Function("No end line".to_string()));
-        }
-        let lines = compute.consume_end_lines(lines)?;
-
-        Ok((compute, lines))
+            .captures(line)
+            .ok_or(Error::Function(line.to_string()))?;
+        Ok(captures.get(1).unwrap().as_str().parse().unwrap())
     }
 }
 
@@ -102,6 +101,23 @@ impl<'a> TryFrom<&'a str> for Function<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::log::Log;
+
+    /// The functions `lines` parse to, with the number of other top-level logs.
+    fn parse_functions<'a>(lines: &'a [&'a str]) -> (Vec<Function<'a>>, usize) {
+        let (functions, others): (Vec<_>, Vec<_>) = Log::from_slice(lines)
+            .inner_logs
+            .into_iter()
+            .partition(|inner_log| matches!(inner_log, InnerLog::Function(_)));
+        let functions = functions
+            .into_iter()
+            .filter_map(|inner_log| match inner_log {
+                InnerLog::Function(function) => Some(function),
+                _ => None,
+            })
+            .collect();
+        (functions, others.len())
+    }
 
     #[test]
     #[tracing_test::traced_test]
@@ -113,13 +129,12 @@ mod tests {
             "Program log: }} // one",
         ];
 
-        let (invoke, remaining_lines) = Function::try_from_slice(SLICE).unwrap();
-        tracing::debug!("{:?}", invoke);
-        tracing::debug!("{:?}", remaining_lines);
-        assert_eq!(remaining_lines.len(), 0);
-        assert_eq!(invoke.id, "one");
-        assert_eq!(invoke.consumption_start, 198708);
-        assert_eq!(invoke.consumption_end, 198082);
+        let (functions, others) = parse_functions(SLICE);
+        tracing::debug!("{:?}", functions);
+        assert_eq!(others, 0);
+        assert_eq!(functions[0].id, "one");
+        assert_eq!(functions[0].consumption_start, 198708);
+        assert_eq!(functions[0].consumption_end, 198082);
 
         const SLICE_WITH_GARBAGE: &[&str] = &[
             "Program log: one {{",
@@ -130,11 +145,12 @@ mod tests {
             "some garbage",
         ];
 
-        let (invoke, remaining_lines) = Function::try_from_slice(SLICE_WITH_GARBAGE).unwrap();
-        assert_eq!(invoke.id, "one");
-        assert_eq!(invoke.consumption_start, 198708);
-        assert_eq!(invoke.consumption_end, 198082);
-        assert_eq!(remaining_lines.len(), 1);
+        let (functions, others) = parse_functions(SLICE_WITH_GARBAGE);
+        assert_eq!(functions[0].id, "one");
+        assert_eq!(functions[0].consumption_start, 198708);
+        assert_eq!(functions[0].consumption_end, 198082);
+        assert_eq!(functions[0].children.len(), 1);
+        assert_eq!(others, 1);
 
         const SLICE_WITH_CHILDREN: &[&str] = &[
             "Program log: one {{",
@@ -147,11 +163,70 @@ mod tests {
             "Program log: }} // one",
         ];
 
-        let (invoke, remaining_lines) = Function::try_from_slice(SLICE_WITH_CHILDREN).unwrap();
-        assert_eq!(invoke.id, "one");
-        assert_eq!(invoke.consumption_start, 198708);
-        assert_eq!(invoke.consumption_end, 196819);
-        assert_eq!(remaining_lines.len(), 0);
-        assert_eq!(invoke.children.len(), 1);
+        let (functions, others) = parse_functions(SLICE_WITH_CHILDREN);
+        assert_eq!(functions[0].id, "one");
+        assert_eq!(functions[0].consumption_start, 198708);
+        assert_eq!(functions[0].consumption_end, 196819);
+        assert_eq!(others, 0);
+        assert_eq!(functions[0].children.len(), 1);
+
+        const SLICE_WITH_PATHS: &[&str] = &[
+            "Program log: Vault::deposit {{",
+            "Program consumption: 198708 units remaining",
+            "Program log: vault::transfer {{",
+            "Program consumption: 197766 units remaining",
+            "Program consumption: 197386 units remaining",
+            "Program log: }} // vault::transfer",
+            "Program consumption: 196819 units remaining",
+            "Program log: }} // Vault::deposit",
+        ];
+
+        let (functions, others) = parse_functions(SLICE_WITH_PATHS);
+        assert_eq!(functions[0].id, "Vault::deposit");
+        assert_eq!(others, 0);
+        assert_eq!(functions[0].children.len(), 1);
+        assert_eq!(functions[0].children[0].id(), Some("vault::transfer"));
+
+        // The end of `Vault::deposit` does not end `deposit`, which never logged its own.
+        const SLICE_WITH_SUFFIX: &[&str] = &[
+            "Program log: Vault::deposit {{",
+            "Program consumption: 198708 units remaining",
+            "Program log: deposit {{",
+            "Program consumption: 197766 units remaining",
+            "Program consumption: 196819 units remaining",
+            "Program log: }} // Vault::deposit",
+        ];
+
+        let (functions, _) = parse_functions(SLICE_WITH_SUFFIX);
+        assert_eq!(functions[0].id, "Vault::deposit");
+        assert_eq!(functions[0].consumption_end, 196819);
+        assert!(functions[0]
+            .children
+            .iter()
+            .all(|child| child.id().is_none()));
+
+        const SLICE_QUALIFIED: &[&str] = &[
+            "Program log: my_program::<Vault<T> as Named>::name {{",
+            "Program consumption: 198708 units remaining",
+            "Program consumption: 198082 units remaining",
+            "Program log: }} // my_program::<Vault<T> as Named>::name",
+        ];
+
+        let (functions, others) = parse_functions(SLICE_QUALIFIED);
+        assert_eq!(functions[0].id, "my_program::<Vault<T> as Named>::name");
+        assert_eq!(functions[0].consumption_end, 198082);
+        assert_eq!(others, 0);
+        assert!(Function::try_from("Program log: not a function {").is_err());
+        for line in [
+            "Program log: not a function {{",
+            "Program log: Instruction: Swap {{",
+            "Program log: Vault<T>deposit {{",
+            "Program log: <A<B<C<D>>>>::name {{",
+        ] {
+            assert!(Function::try_from(line).is_err(), "{}", line);
+        }
+        let name = "crate::<&'a mut Vault<Box<T>> as Named<'a, T>>::name";
+        let line = format!("Program log: {} {{{{", name);
+        assert_eq!(Function::try_from(line.as_str()).unwrap().id, name);
     }
 }
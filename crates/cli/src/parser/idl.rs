@@ -0,0 +1,884 @@
+extern crate borsh;
+extern crate bs58;
+extern crate serde;
+extern crate serde_json;
+extern crate sol_dev_utils;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use self::borsh::BorshDeserialize;
+use self::serde::{Deserialize, Serialize};
+use self::serde_json::{Map, Value};
+
+use super::{
+    error::Error,
+    log::{Entry, InnerLog},
+    payload::Bytes,
+};
+
+/// An Anchor event decoded from a `Program data:` line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub name: String,
+    pub fields: Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdlFile {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    address: Option<String>,
+    #[serde(default)]
+    metadata: IdlMetadata,
+    /// Read one at a time, so that one the decoder does not support does not fail
+    /// the others, see `Events::add_idl`.
+    #[serde(default)]
+    events: Vec<Value>,
+    #[serde(default)]
+    types: Vec<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct IdlMetadata {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    address: Option<String>,
+}
+
+/// The files in `paths`, which are files or directories, where only the files
+/// with one of `extensions` are taken from directories.
+pub fn files<P: AsRef<Path>>(paths: &[P], extensions: &[&str]) -> Result<Vec<PathBuf>, Error> {
+    let mut files = vec![];
+    for path in paths {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            files.push(path.to_path_buf());
+            continue;
+        }
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| extensions.contains(&e))
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The address and name of the program an IDL describes.
+///
+/// Since Anchor 0.30 both are always there, before that the address is only
+/// added to the metadata on deploy.
+pub fn program(idl: &str) -> Result<Option<(String, String)>, Error> {
+    let file: IdlFile = serde_json::from_str(idl)?;
+    let address = file.address.or(file.metadata.address);
+    let name = file.metadata.name.or(file.name);
+    Ok(address.zip(name))
+}
+
+/// Before Anchor 0.30 events list their fields, since then they refer to a type
+/// of the same name and carry their discriminator.
+#[derive(Debug, Deserialize)]
+struct IdlEvent {
+    name: String,
+    #[serde(default)]
+    discriminator: Option<[u8; 8]>,
+    #[serde(default)]
+    fields: Option<Vec<IdlField>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdlField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: IdlType,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdlTypeDef {
+    name: String,
+    #[serde(rename = "type")]
+    ty: IdlTypeDefTy,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase", tag = "kind")]
+enum IdlTypeDefTy {
+    Struct {
+        #[serde(default)]
+        fields: Option<IdlFields>,
+    },
+    Enum {
+        variants: Vec<IdlVariant>,
+    },
+    Type {
+        alias: IdlType,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum IdlFields {
+    Named(Vec<IdlField>),
+    Tuple(Vec<IdlType>),
+}
+
+#[derive(Debug, Deserialize)]
+struct IdlVariant {
+    name: String,
+    #[serde(default)]
+    fields: Option<IdlFields>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum IdlType {
+    Primitive(String),
+    Compound(IdlCompound),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum IdlCompound {
+    Vec(Box<IdlType>),
+    Option(Box<IdlType>),
+    COption(Box<IdlType>),
+    Array(Box<IdlType>, usize),
+    Defined(IdlDefined),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum IdlDefined {
+    Name(String),
+    Named { name: String },
+}
+
+impl IdlDefined {
+    fn name(&self) -> &str {
+        match self {
+            IdlDefined::Name(name) | IdlDefined::Named { name } => name,
+        }
+    }
+}
+
+/// The events and types of one IDL.
+#[derive(Debug)]
+struct Idl {
+    /// The program the IDL describes, when the IDL says.
+    address: Option<String>,
+    events: HashMap<[u8; 8], (String, IdlFields)>,
+    types: HashMap<String, IdlTypeDefTy>,
+}
+
+/// Anchor events that can be decoded, loaded from IDLs.
+///
+/// Events are recognised by their discriminant, the first 8 bytes of the
+/// SHA-256 hash of `event:<name>`, and their fields are Borsh-decoded.
+/// An event is looked up in the IDL of the program that logged it, and only in all
+/// of them when no IDL has the address of that program.
+#[derive(Debug, Default)]
+pub struct Events {
+    idls: Vec<Idl>,
+    /// Index in `idls` by program address.
+    programs: HashMap<String, usize>,
+}
+
+impl Events {
+    pub fn new() -> Events {
+        Events::default()
+    }
+
+    /// Loads every IDL in `paths`, which are IDL files or directories of them.
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Events, Error> {
+        let mut events = Events::new();
+        for path in files(paths, &["json"])? {
+            events.load_file(&path)?;
+        }
+        Ok(events)
+    }
+
+    fn load_file(&mut self, path: &Path) -> Result<(), Error> {
+        let idl = std::fs::read_to_string(path)?;
+        self.add_idl(&idl)
+            .map_err(|e| Error::Idl(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Adds the events of an IDL in JSON.
+    ///
+    /// Events that cannot be decoded, such as those with generic types, are skipped
+    /// with a warning.
+    pub fn add_idl(&mut self, idl: &str) -> Result<(), Error> {
+        let file: IdlFile = serde_json::from_str(idl)?;
+        let address = file.address.or(file.metadata.address);
+        // Types that do not parse are missing below, and so are the events using them.
+        let mut types = file
+            .types
+            .into_iter()
+            .filter_map(|def| serde_json::from_value::<IdlTypeDef>(def).ok())
+            .map(|def| (def.name, def.ty))
+            .collect::<HashMap<_, _>>();
+        let events = file
+            .events
+            .into_iter()
+            .map(|event| {
+                let name = event
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+                (name, Idl::event(event, &mut types))
+            })
+            .collect::<Vec<_>>();
+        let mut idl = Idl {
+            address,
+            events: HashMap::new(),
+            types,
+        };
+        for (name, event) in events {
+            let name = name.unwrap_or_default();
+            let checked = event.and_then(|(discriminant, fields)| {
+                idl.check_fields(&fields, &mut HashSet::new())?;
+                Ok((discriminant, fields))
+            });
+            match checked {
+                Ok((discriminant, fields)) => {
+                    idl.events.insert(discriminant, (name, fields));
+                }
+                Err(e) => tracing::warn!("Skipping event {}, which cannot be decoded: {}", name, e),
+            }
+        }
+        if let Some(address) = &idl.address {
+            self.programs.insert(address.clone(), self.idls.len());
+        }
+        self.idls.push(idl);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.idls.iter().all(|idl| idl.events.is_empty())
+    }
+
+    /// Decodes `data`, the single slice of a `Program data:` line logged by `program`,
+    /// if it is a known event.
+    pub fn decode(&self, program: Option<&str>, data: &[u8]) -> Option<Event> {
+        let (discriminant, mut rest) = (data.get(..8)?, data.get(8..)?);
+        let idls = match program.and_then(|program| self.programs.get(program)) {
+            Some(&index) => &self.idls[index..=index],
+            None => &self.idls[..],
+        };
+        let (idl, (name, fields)) = idls
+            .iter()
+            .find_map(|idl| Some((idl, idl.events.get(discriminant)?)))?;
+        match idl.decode_fields(fields, &mut rest, 0) {
+            Ok(Value::Object(fields)) => Some(Event {
+                name: name.clone(),
+                fields,
+            }),
+            Ok(_) => None,
+            Err(e) => {
+                tracing::debug!("could not decode event {}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    /// Decodes the events of every `Program data:` line in `entry`.
+    pub fn decode_entry(&self, entry: &mut Entry) {
+        if !self.is_empty() {
+            self.decode_all(None, entry.inner_logs_mut());
+        }
+    }
+
+    /// `program` is the address of the enclosing invoke.
+    fn decode_all(&self, program: Option<&str>, inner_logs: &mut [InnerLog]) {
+        for inner_log in inner_logs {
+            let id;
+            let program = match inner_log {
+                InnerLog::Invoke(invoke) => {
+                    id = invoke.id.to_string();
+                    Some(id.as_str())
+                }
+                InnerLog::Data(data) => {
+                    if let [Bytes(bytes)] = data.data.as_slice() {
+                        data.event = self.decode(program, bytes);
+                    }
+                    program
+                }
+                _ => program,
+            };
+            self.decode_all(program, inner_log.inner_logs_mut());
+        }
+    }
+}
+
+impl Idl {
+    /// The discriminant and fields of `event`, taking the struct of the same name out
+    /// of `types` where it has no fields of its own.
+    fn event(
+        event: Value,
+        types: &mut HashMap<String, IdlTypeDefTy>,
+    ) -> Result<([u8; 8], IdlFields), Error> {
+        let event: IdlEvent = serde_json::from_value(event)?;
+        let discriminant = event.discriminator.unwrap_or_else(|| {
+            sol_dev_utils::anchor_discriminant(&format!("event:{}", event.name))
+        });
+        let fields = match event.fields {
+            Some(fields) => IdlFields::Named(fields),
+            None => match types.remove(&event.name) {
+                Some(IdlTypeDefTy::Struct { fields }) => fields.unwrap_or(IdlFields::Named(vec![])),
+                _ => return Err(Error::Idl(format!("no struct for event {}", event.name))),
+            },
+        };
+        Ok((discriminant, fields))
+    }
+
+    /// Fails if `decode_fields` cannot decode `fields`, whatever the data.
+    /// `seen` holds the defined types already checked, which may be recursive.
+    fn check_fields<'b>(
+        &'b self,
+        fields: &'b IdlFields,
+        seen: &mut HashSet<&'b str>,
+    ) -> Result<(), Error> {
+        match fields {
+            IdlFields::Named(fields) => fields
+                .iter()
+                .try_for_each(|field| self.check(&field.ty, seen)),
+            IdlFields::Tuple(types) => types.iter().try_for_each(|ty| self.check(ty, seen)),
+        }
+    }
+
+    fn check<'b>(&'b self, ty: &'b IdlType, seen: &mut HashSet<&'b str>) -> Result<(), Error> {
+        match ty {
+            IdlType::Primitive(ty) if PRIMITIVES.contains(&ty.as_str()) => Ok(()),
+            IdlType::Primitive(ty) => Err(Error::Idl(format!("unsupported type {}", ty))),
+            IdlType::Compound(IdlCompound::Vec(ty))
+            | IdlType::Compound(IdlCompound::Array(ty, _))
+            | IdlType::Compound(IdlCompound::Option(ty))
+            | IdlType::Compound(IdlCompound::COption(ty)) => self.check(ty, seen),
+            IdlType::Compound(IdlCompound::Defined(defined)) => {
+                let name = defined.name();
+                if !seen.insert(name) {
+                    return Ok(());
+                }
+                self.consumes(ty, &mut vec![])?;
+                match self.types.get(name) {
+                    Some(IdlTypeDefTy::Struct { fields: None }) => Ok(()),
+                    Some(IdlTypeDefTy::Struct {
+                        fields: Some(fields),
+                    }) => self.check_fields(fields, seen),
+                    Some(IdlTypeDefTy::Enum { variants }) => variants
+                        .iter()
+                        .filter_map(|variant| variant.fields.as_ref())
+                        .try_for_each(|fields| self.check_fields(fields, seen)),
+                    Some(IdlTypeDefTy::Type { alias }) => self.check(alias, seen),
+                    None => Err(Error::Idl(format!("unknown type {}", name))),
+                }
+            }
+        }
+    }
+
+    /// `depth` is the number of defined types being decoded, see `MAX_DEPTH`.
+    /// Whether decoding `ty` always takes a byte or more, failing on a type that
+    /// contains itself before taking any, which `decode` would never finish.
+    /// `open` holds the defined types being decoded that have taken no byte yet.
+    fn consumes<'b>(&'b self, ty: &'b IdlType, open: &mut Vec<&'b str>) -> Result<bool, Error> {
+        match ty {
+            IdlType::Primitive(_)
+            | IdlType::Compound(IdlCompound::Vec(_))
+            | IdlType::Compound(IdlCompound::Option(_))
+            | IdlType::Compound(IdlCompound::COption(_)) => Ok(true),
+            IdlType::Compound(IdlCompound::Array(_, 0)) => Ok(false),
+            IdlType::Compound(IdlCompound::Array(ty, _)) => self.consumes(ty, open),
+            IdlType::Compound(IdlCompound::Defined(defined)) => {
+                let name = defined.name();
+                if open.contains(&name) {
+                    return Err(Error::Idl(format!("{} contains itself", name)));
+                }
+                open.push(name);
+                let consumes = match self.types.get(name) {
+                    Some(IdlTypeDefTy::Struct {
+                        fields: Some(IdlFields::Named(fields)),
+                    }) => self.any_consumes(fields.iter().map(|field| &field.ty), open)?,
+                    Some(IdlTypeDefTy::Struct {
+                        fields: Some(IdlFields::Tuple(types)),
+                    }) => self.any_consumes(types, open)?,
+                    Some(IdlTypeDefTy::Enum { .. }) => true,
+                    Some(IdlTypeDefTy::Type { alias }) => self.consumes(alias, open)?,
+                    Some(IdlTypeDefTy::Struct { fields: None }) | None => false,
+                };
+                open.pop();
+                Ok(consumes)
+            }
+        }
+    }
+
+    /// Whether any of `types`, decoded in order, takes a byte. Those after the first
+    /// that does are decoded with bytes taken, so are not looked at.
+    fn any_consumes<'b>(
+        &'b self,
+        types: impl IntoIterator<Item = &'b IdlType>,
+        open: &mut Vec<&'b str>,
+    ) -> Result<bool, Error> {
+        for ty in types {
+            if self.consumes(ty, open)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn decode_fields(
+        &self,
+        fields: &IdlFields,
+        data: &mut &[u8],
+        depth: usize,
+    ) -> Result<Value, Error> {
+        Ok(match fields {
+            IdlFields::Named(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|field| Ok((field.name.clone(), self.decode(&field.ty, data, depth)?)))
+                    .collect::<Result<_, Error>>()?,
+            ),
+            IdlFields::Tuple(types) => Value::Array(
+                types
+                    .iter()
+                    .map(|ty| self.decode(ty, data, depth))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+
+    fn decode(&self, ty: &IdlType, data: &mut &[u8], depth: usize) -> Result<Value, Error> {
+        Ok(match ty {
+            IdlType::Primitive(ty) => match ty.as_str() {
+                "bool" => Value::from(read::<bool>(data)?),
+                "u8" => Value::from(read::<u8>(data)?),
+                "i8" => Value::from(read::<i8>(data)?),
+                "u16" => Value::from(read::<u16>(data)?),
+                "i16" => Value::from(read::<i16>(data)?),
+                "u32" => Value::from(read::<u32>(data)?),
+                "i32" => Value::from(read::<i32>(data)?),
+                "u64" => Value::from(read::<u64>(data)?),
+                "i64" => Value::from(read::<i64>(data)?),
+                // Too large for JSON numbers.
+                "u128" => Value::from(read::<u128>(data)?.to_string()),
+                "i128" => Value::from(read::<i128>(data)?.to_string()),
+                "f32" => Value::from(read::<f32>(data)?),
+                "f64" => Value::from(read::<f64>(data)?),
+                "string" => Value::from(read::<String>(data)?),
+                "bytes" => Value::from(Bytes(read::<Vec<u8>>(data)?).to_hex()),
+                "publicKey" | "pubkey" => {
+                    Value::from(bs58::encode(read::<[u8; 32]>(data)?).into_string())
+                }
+                ty => return Err(Error::Idl(format!("unsupported type {}", ty))),
+            },
+            IdlType::Compound(IdlCompound::Vec(ty)) => {
+                let len = read::<u32>(data)?;
+                // Every element but an empty struct takes a byte or more, so a longer
+                // vec is corrupt. It is taken to be for empty structs too, rather than
+                // decoding up to `u32::MAX` of them.
+                if len as usize > data.len() {
+                    return Err(Error::Idl(format!(
+                        "vec of {} in {} bytes",
+                        len,
+                        data.len()
+                    )));
+                }
+                Value::Array(
+                    (0..len)
+                        .map(|_| self.decode(ty, data, depth))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            IdlType::Compound(IdlCompound::Array(ty, len)) => Value::Array(
+                (0..*len)
+                    .map(|_| self.decode(ty, data, depth))
+                    .collect::<Result<_, _>>()?,
+            ),
+            IdlType::Compound(IdlCompound::Option(ty)) => match read::<u8>(data)? {
+                0 => Value::Null,
+                _ => self.decode(ty, data, depth)?,
+            },
+            IdlType::Compound(IdlCompound::COption(ty)) => match read::<u32>(data)? {
+                0 => Value::Null,
+                _ => self.decode(ty, data, depth)?,
+            },
+            IdlType::Compound(IdlCompound::Defined(defined)) => {
+                let depth = depth + 1;
+                if depth > MAX_DEPTH {
+                    return Err(Error::Idl(format!(
+                        "{} nested deeper than {}",
+                        defined.name(),
+                        MAX_DEPTH
+                    )));
+                }
+                match self.types.get(defined.name()) {
+                    Some(IdlTypeDefTy::Struct { fields: None }) => Value::Object(Map::new()),
+                    Some(IdlTypeDefTy::Struct {
+                        fields: Some(fields),
+                    }) => self.decode_fields(fields, data, depth)?,
+                    Some(IdlTypeDefTy::Enum { variants }) => {
+                        let index = read::<u8>(data)? as usize;
+                        let variant = variants.get(index).ok_or_else(|| {
+                            Error::Idl(format!("no variant {} of {}", index, defined.name()))
+                        })?;
+                        match &variant.fields {
+                            None => Value::from(variant.name.clone()),
+                            Some(fields) => {
+                                let mut object = Map::new();
+                                object.insert(
+                                    variant.name.clone(),
+                                    self.decode_fields(fields, data, depth)?,
+                                );
+                                Value::Object(object)
+                            }
+                        }
+                    }
+                    Some(IdlTypeDefTy::Type { alias }) => self.decode(alias, data, depth)?,
+                    None => return Err(Error::Idl(format!("unknown type {}", defined.name()))),
+                }
+            }
+        })
+    }
+}
+
+/// How deep `Idl::decode` nests defined types, so that a recursive type cannot
+/// exhaust the stack on a long payload.
+const MAX_DEPTH: usize = 32;
+
+/// The primitive types `Idl::decode` supports.
+const PRIMITIVES: &[&str] = &[
+    "bool",
+    "u8",
+    "i8",
+    "u16",
+    "i16",
+    "u32",
+    "i32",
+    "u64",
+    "i64",
+    "u128",
+    "i128",
+    "f32",
+    "f64",
+    "string",
+    "bytes",
+    "publicKey",
+    "pubkey",
+];
+
+fn read<T: BorshDeserialize>(data: &mut &[u8]) -> Result<T, Error> {
+    T::deserialize(data).map_err(|e| Error::Idl(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate base64;
+
+    use self::base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    use super::*;
+    use crate::parser::fixtures;
+
+    const LEGACY_IDL: &str = r#"{
+        "version": "0.1.0",
+        "name": "some_program",
+        "instructions": [],
+        "metadata": { "address": "LEGACY_PROGRAM" },
+        "events": [
+            {
+                "name": "SomeEvent",
+                "fields": [
+                    { "name": "version", "type": "u8", "index": false },
+                    { "name": "msg", "type": "string", "index": false },
+                    { "name": "amounts", "type": { "vec": "u64" }, "index": false },
+                    { "name": "side", "type": { "defined": "Side" }, "index": false }
+                ]
+            }
+        ],
+        "types": [
+            { "name": "Side", "type": { "kind": "enum", "variants": [{ "name": "Bid" }, { "name": "Ask" }] } }
+        ]
+    }"#;
+
+    const IDL: &str = r#"{
+        "address": "SOME_PROGRAM",
+        "instructions": [],
+        "events": [{ "name": "OtherEvent", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8] }],
+        "types": [
+            {
+                "name": "OtherEvent",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "owner", "type": "pubkey" },
+                        { "name": "limit", "type": { "option": "u32" } }
+                    ]
+                }
+            }
+        ]
+    }"#;
+
+    /// Has an event of the same name as `LEGACY_IDL`, so with the same discriminant,
+    /// but other fields.
+    const CLASHING_IDL: &str = r#"{
+        "version": "0.1.0",
+        "name": "clashing_program",
+        "instructions": [],
+        "metadata": { "address": "CLASHING_PROGRAM" },
+        "events": [{ "name": "SomeEvent", "fields": [{ "name": "value", "type": "u8" }] }]
+    }"#;
+
+    fn some_event() -> Vec<u8> {
+        let mut data = sol_dev_utils::anchor_discriminant("event:SomeEvent").to_vec();
+        data.extend(borsh::to_vec(&(0u8, "Hello, World!".to_string(), vec![1u64, 2])).unwrap());
+        data.push(1);
+        data
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_decode() {
+        let mut events = Events::new();
+        events.add_idl(LEGACY_IDL).unwrap();
+        events.add_idl(IDL).unwrap();
+
+        let event = events.decode(None, &some_event()).unwrap();
+        assert_eq!(event.name, "SomeEvent");
+        assert_eq!(
+            Value::Object(event.fields),
+            serde_json::json!({
+                "version": 0,
+                "msg": "Hello, World!",
+                "amounts": [1, 2],
+                "side": "Ask",
+            })
+        );
+
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend([0; 32]);
+        data.push(0);
+        let event = events.decode(None, &data).unwrap();
+        assert_eq!(event.name, "OtherEvent");
+        assert_eq!(
+            Value::Object(event.fields),
+            serde_json::json!({
+                "owner": "11111111111111111111111111111111",
+                "limit": null,
+            })
+        );
+
+        // Unknown discriminant, and a known one with too little data.
+        assert_eq!(events.decode(None, &[0; 16]), None);
+        assert_eq!(events.decode(None, &some_event()[..12]), None);
+    }
+
+    const UNSUPPORTED_IDL: &str = r#"{
+        "address": "OTHER_PROGRAM",
+        "instructions": [],
+        "events": [
+            { "name": "GenericEvent", "discriminator": [1, 1, 1, 1, 1, 1, 1, 1] },
+            { "name": "FloatEvent", "discriminator": [2, 2, 2, 2, 2, 2, 2, 2] },
+            { "name": "EmptyEvent", "discriminator": [3, 3, 3, 3, 3, 3, 3, 3] },
+            { "name": "MissingEvent", "discriminator": [4, 4, 4, 4, 4, 4, 4, 4] }
+        ],
+        "types": [
+            {
+                "name": "GenericEvent",
+                "type": {
+                    "kind": "struct",
+                    "fields": [{ "name": "inner", "type": { "defined": { "name": "Wrapper" } } }]
+                }
+            },
+            {
+                "name": "Wrapper",
+                "generics": [{ "kind": "type", "name": "T" }],
+                "type": { "kind": "struct", "fields": [{ "name": "value", "type": { "generic": "T" } }] }
+            },
+            {
+                "name": "FloatEvent",
+                "type": { "kind": "struct", "fields": [{ "name": "value", "type": "f16" }] }
+            },
+            {
+                "name": "EmptyEvent",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "units", "type": { "vec": { "defined": { "name": "Unit" } } } },
+                        { "name": "tag", "type": "u16" }
+                    ]
+                }
+            },
+            { "name": "Unit", "type": { "kind": "struct" } }
+        ]
+    }"#;
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_skip_unsupported() {
+        let mut events = Events::new();
+        events.add_idl(UNSUPPORTED_IDL).unwrap();
+        assert_eq!(events.idls[0].events.len(), 1);
+        assert!(logs_contain("Skipping event GenericEvent"));
+        assert!(logs_contain("Skipping event FloatEvent"));
+        assert!(logs_contain("Skipping event MissingEvent"));
+
+        let mut data = vec![3; 8];
+        data.extend(2u32.to_le_bytes());
+        data.extend(7u16.to_le_bytes());
+        let event = events.decode(None, &data).unwrap();
+        assert_eq!(
+            Value::Object(event.fields),
+            serde_json::json!({ "units": [{}, {}], "tag": 7 })
+        );
+
+        // More empty structs than bytes left.
+        data.splice(8..12, u32::MAX.to_le_bytes());
+        assert_eq!(events.decode(None, &data), None);
+    }
+
+    const RECURSIVE_IDL: &str = r#"{
+        "address": "OTHER_PROGRAM",
+        "instructions": [],
+        "events": [
+            { "name": "AliasEvent", "discriminator": [1, 1, 1, 1, 1, 1, 1, 1] },
+            { "name": "NodeEvent", "discriminator": [2, 2, 2, 2, 2, 2, 2, 2] },
+            { "name": "ListEvent", "discriminator": [3, 3, 3, 3, 3, 3, 3, 3] }
+        ],
+        "types": [
+            {
+                "name": "AliasEvent",
+                "type": { "kind": "struct", "fields": [{ "name": "value", "type": { "defined": { "name": "Loop" } } }] }
+            },
+            { "name": "Loop", "type": { "kind": "type", "alias": { "defined": { "name": "Loop" } } } },
+            {
+                "name": "NodeEvent",
+                "type": { "kind": "struct", "fields": [{ "name": "node", "type": { "defined": { "name": "Node" } } }] }
+            },
+            {
+                "name": "Node",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "children", "type": { "array": [{ "defined": { "name": "Node" } }, 2] } },
+                        { "name": "value", "type": "u8" }
+                    ]
+                }
+            },
+            {
+                "name": "ListEvent",
+                "type": { "kind": "struct", "fields": [{ "name": "list", "type": { "defined": { "name": "List" } } }] }
+            },
+            {
+                "name": "List",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "value", "type": "u8" },
+                        { "name": "next", "type": { "option": { "defined": { "name": "List" } } } }
+                    ]
+                }
+            }
+        ]
+    }"#;
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_skip_recursive() {
+        let mut events = Events::new();
+        events.add_idl(RECURSIVE_IDL).unwrap();
+        // Types that contain themselves before any byte would never finish decoding.
+        assert!(logs_contain("Skipping event AliasEvent"));
+        assert!(logs_contain("Skipping event NodeEvent"));
+        assert_eq!(events.idls[0].events.len(), 1);
+        assert_eq!(events.decode(None, &[1; 16]), None);
+
+        // Lists take a byte a node, but are only decoded to `MAX_DEPTH` nodes.
+        let list = |len: usize| {
+            let mut data = vec![3; 8];
+            for value in 0..len {
+                data.extend([value as u8, (value + 1 < len) as u8]);
+            }
+            data
+        };
+        let event = events.decode(None, &list(2)).unwrap();
+        assert_eq!(
+            Value::Object(event.fields),
+            serde_json::json!({ "list": { "value": 0, "next": { "value": 1, "next": null } } })
+        );
+        assert!(events.decode(None, &list(MAX_DEPTH)).is_some());
+        assert_eq!(events.decode(None, &list(MAX_DEPTH + 1)), None);
+        assert_eq!(events.decode(None, &list(10_000)), None);
+    }
+
+    /// The events decoded anywhere in `inner_logs`, in order.
+    fn decoded<'b>(inner_logs: &'b [InnerLog], events: &mut Vec<&'b Event>) {
+        for inner_log in inner_logs {
+            if let InnerLog::Data(data) = inner_log {
+                events.extend(&data.event);
+            }
+            decoded(inner_log.inner_logs(), events);
+        }
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_decode_entry() {
+        let mut events = Events::new();
+        events.add_idl(CLASHING_IDL).unwrap();
+        events.add_idl(LEGACY_IDL).unwrap();
+        let data = format!("Program data: {}", STANDARD.encode(some_event()));
+        let lines = [
+            "Program LEGACY_PROGRAM invoke [1]",
+            "Program log: fn_one {{",
+            "Program consumption: 199000 units remaining",
+            data.as_str(),
+            "Program CLASHING_PROGRAM invoke [2]",
+            data.as_str(),
+            "Program CLASHING_PROGRAM consumed 1000 of 197500 compute units",
+            "Program CLASHING_PROGRAM success",
+            "Program consumption: 196000 units remaining",
+            "Program log: }} // fn_one",
+            data.as_str(),
+            "Program LEGACY_PROGRAM consumed 4000 of 200000 compute units",
+            "Program LEGACY_PROGRAM success",
+            "Program SOME_PROGRAM invoke [1]",
+            data.as_str(),
+            "Program SOME_PROGRAM consumed 1000 of 200000 compute units",
+            "Program SOME_PROGRAM success",
+        ];
+        let mut entries = fixtures::entries(&lines);
+        let mut found = vec![];
+        for entry in &mut entries {
+            events.decode_entry(entry);
+        }
+        for entry in &entries {
+            decoded(entry.inner_logs(), &mut found);
+        }
+        let legacy = serde_json::json!({
+            "version": 0,
+            "msg": "Hello, World!",
+            "amounts": [1, 2],
+            "side": "Ask",
+        });
+        let clashing = serde_json::json!({ "value": 0 });
+        // Decoded with the IDL of the program that logged it, or the first one that
+        // knows the event for a program without an IDL.
+        assert_eq!(
+            found
+                .iter()
+                .map(|event| Value::Object(event.fields.clone()))
+                .collect::<Vec<_>>(),
+            vec![legacy.clone(), clashing.clone(), legacy, clashing]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::io::BufRead;
+
+use super::{
+    error::Error,
+    invoke::LOG_TRUNCATED,
+    log::{Entry, InnerLog},
+    stream::{self, StreamParser},
+    transaction,
+};
+
+/// The format of an input file.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// Detect the format from the start of the file.
+    Auto,
+    /// A JSON array of log lines.
+    Json,
+    /// Newline-delimited logs, as printed by `solana logs`, `solana-test-validator`
+    /// or `cargo test-sbf`.
+    Text,
+    /// One or many RPC `getTransaction` responses, as saved from the RPC
+    /// or printed by `solana confirm -v --output json`.
+    Rpc,
+}
+
+impl Input {
+    /// Peeks at the start of `reader` without consuming it.
+    /// A JSON array of strings starts with `[` followed by `"` or `]`, RPC responses
+    /// start with `{` or `[{`; anything else, including `[<timestamp> DEBUG ...]`
+    /// prefixed lines, is treated as text.
+    pub fn detect<R: BufRead>(reader: &mut R) -> Result<Input, Error> {
+        let mut bytes = reader
+            .fill_buf()?
+            .iter()
+            .filter(|b| !b.is_ascii_whitespace());
+        Ok(match (bytes.next(), bytes.next()) {
+            (Some(b'['), Some(b'"')) | (Some(b'['), Some(b']')) => Input::Json,
+            (Some(b'{'), _) | (Some(b'['), Some(b'{')) => Input::Rpc,
+            _ => Input::Text,
+        })
+    }
+
+    /// Parses `reader`, handing every completed top-level entry to `emit`.
+    pub fn parse<R, F>(self, mut reader: R, mut emit: F) -> Result<(), Error>
+    where
+        R: BufRead,
+        F: FnMut(Entry<'static>) -> Result<(), Error>,
+    {
+        match self {
+            Input::Auto => Input::detect(&mut reader)?.parse(reader, emit),
+            Input::Json => {
+                stream::parse_json_array(reader, |inner_log| emit(Entry::InnerLog(inner_log)))
+            }
+            Input::Text => parse_text(reader, |inner_log| emit(Entry::InnerLog(inner_log))),
+            Input::Rpc => transaction::parse_responses(reader, |transaction| {
+                emit(Entry::Transaction(transaction))
+            }),
+        }
+    }
+}
+
+/// Strips everything in front of the program output on a plain-text line.
+/// Returns `None` for lines that are not program output, such as the
+/// `Signature:` and `Status:` headers of `solana logs`.
+///
+/// Handles lines like:
+/// - `    Program log: Instruction: Transfer`
+/// - `[2024-09-01T12:00:00.000000000Z DEBUG solana_runtime::message_processor::stable_log] Program log: Instruction: Transfer`
+pub fn strip_prefix(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let line = match line.strip_prefix('[') {
+        Some(rest) => rest
+            .split_once("] ")
+            .map_or(line, |(_, rest)| rest.trim_start()),
+        None => line,
+    };
+    if line.starts_with("Program ") || line == LOG_TRUNCATED {
+        Some(line)
+    } else {
+        None
+    }
+}
+
+fn parse_text<R, F>(reader: R, mut emit: F) -> Result<(), Error>
+where
+    R: BufRead,
+    F: FnMut(InnerLog<'static>) -> Result<(), Error>,
+{
+    let mut parser = StreamParser::new();
+    for line in reader.lines() {
+        match strip_prefix(&line?) {
+            Some(line) => parser.push(line.to_string()),
+            None => parser.skip(),
+        }
+        while let Some(inner_log) = parser.pop() {
+            emit(inner_log)?;
+        }
+    }
+    parser.finish();
+    while let Some(inner_log) = parser.pop() {
+        emit(inner_log)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixtures::CALL;
+
+    /// `CALL` as `cargo test-sbf` prints it.
+    fn test_sbf() -> String {
+        let mut text = "running 1 test\n".to_string();
+        for (i, line) in CALL.iter().enumerate() {
+            text += &format!(
+                "[2024-09-01T12:00:00.00000000{}Z DEBUG solana_runtime::message_processor::stable_log] {}\n",
+                i + 1,
+                line
+            );
+        }
+        text + "test test_fn_one ... ok\n"
+    }
+
+    /// `CALL` as `solana confirm -v` prints it.
+    fn solana_logs() -> String {
+        let mut text = "\
+Transaction executed in slot 5:
+  Signature: 2rE5u5ts2RF6p8XGpqRyTgRp9Z7MgzUXqgXVxx1pb5kV
+  Status: Ok
+  Log Messages:
+"
+        .to_string();
+        for line in CALL {
+            text += &format!("    {}\n", line);
+        }
+        text
+    }
+
+    fn parse(input: &str) -> (Input, serde_json::Value) {
+        let mut reader = input.as_bytes();
+        let format = Input::detect(&mut reader).unwrap();
+        let mut entries = vec![];
+        Input::Auto
+            .parse(reader, |entry| {
+                entries.push(entry);
+                Ok(())
+            })
+            .unwrap();
+        (format, serde_json::to_value(&entries).unwrap())
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_detect_and_parse() {
+        let json = serde_json::to_string_pretty(CALL).unwrap();
+        let (format, expected) = parse(&json);
+        assert_eq!(format, Input::Json);
+        assert_eq!(expected.as_array().unwrap().len(), 1);
+
+        let (format, parsed) = parse(&test_sbf());
+        assert_eq!(format, Input::Text);
+        assert_eq!(parsed, expected);
+
+        let (format, parsed) = parse(&solana_logs());
+        assert_eq!(format, Input::Text);
+        assert_eq!(parsed, expected);
+
+        assert_eq!(parse("[]").0, Input::Json);
+
+        let rpc = format!(
+            r#"[{{"slot": 5, "meta": {{"computeUnitsConsumed": 7218, "logMessages": {}}}}}]"#,
+            json
+        );
+        let (format, parsed) = parse(&rpc);
+        assert_eq!(format, Input::Rpc);
+        assert_eq!(parsed[0]["slot"], 5);
+        assert_eq!(parsed[0]["logs"], expected);
+    }
+}
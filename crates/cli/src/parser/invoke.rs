@@ -2,24 +2,112 @@ extern crate lazy_static;
 extern crate regex;
 extern crate serde;
 
+use std::borrow::Cow;
 use std::convert::TryFrom;
 
 use self::regex::Regex;
 use self::serde::{Deserialize, Serialize};
 
-use super::{error::Error, log::InnerLog};
+use super::{
+    calibration::{Calibration, LogCost},
+    error::Error,
+    log::InnerLog,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Invoke<'a> {
-    pub id: &'a str,
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
+    /// Human readable name of the program, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The instruction the program logged it handles, e.g. `Transfer`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instruction: Option<String>,
     pub depth: u32,
     #[serde(skip)]
     pub consumption: u32,
+    #[serde(skip)]
+    pub budget: u32,
+    #[serde(skip)]
+    pub log_cost: LogCost,
+    /// What the caller pays to invoke the program, on top of what the program consumes.
+    #[serde(skip)]
+    pub cpi_cost: i32,
+    /// How the invoke ended, once it has.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Outcome>,
     pub children: Vec<InnerLog<'a>>,
+    /// Children hidden by a `Filter`, which still count towards the consumption.
+    #[serde(skip)]
+    pub pruned: Vec<InnerLog<'a>>,
+}
+
+/// What an invoke is called in call paths, see `Invoke::label`. Shared with invokes
+/// read back from parsed files, so that `diff` and `aggregate` agree.
+pub fn label(id: &str, name: Option<&str>, instruction: Option<&str>) -> String {
+    let program = name.unwrap_or(id);
+    match instruction {
+        Some(instruction) => format!("{}::{}", program, instruction),
+        None => program.to_string(),
+    }
+}
+
+/// Prefix of the message naming the instruction an invoke handles.
+pub const INSTRUCTION: &str = "Instruction: ";
+
+/// Logged by the runtime in place of any further lines once the log limit is reached.
+pub const LOG_TRUNCATED: &str = "Log truncated";
+
+/// How an invoke ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum Outcome {
+    Success,
+    Failed {
+        error: String,
+    },
+    /// The runtime stopped recording logs, see `Log truncated`.
+    Truncated,
+    /// The invoke ran out of compute units.
+    ExceededBudget {
+        error: String,
+    },
+}
+
+/// How much of its compute budget an invoke, or a whole transaction, used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetUsage {
+    pub consumed: u32,
+    pub budget: u32,
+    pub headroom: u32,
+    pub percent_of_budget: f64,
+}
+
+impl BudgetUsage {
+    pub fn new(consumed: u32, budget: u32) -> BudgetUsage {
+        BudgetUsage {
+            consumed,
+            budget,
+            headroom: budget.saturating_sub(consumed),
+            percent_of_budget: match budget {
+                0 => 0.0,
+                budget => consumed as f64 / budget as f64 * 100.0,
+            },
+        }
+    }
+}
+
+impl Outcome {
+    pub fn is_success(&self) -> bool {
+        matches!(self, Outcome::Success)
+    }
 }
 
 lazy_static::lazy_static! {
     static ref RE_START: Regex = Regex::new(r"Program (\w+) invoke \[(\d+)\]").unwrap();
+    static ref RE_END: Regex = Regex::new(r"Program (\w+) (success|failed)").unwrap();
+    static ref RE_FAILED: Regex = Regex::new(r"Program (\w+) failed: (.*)").unwrap();
     static ref RE_CONSUMPTION: Regex = Regex::new(r"Program (\w+) consumed (\d+) of (\d+) compute units").unwrap();
 }
 
@@ -37,15 +125,20 @@ impl<'a> TryFrom<&'a str> for Invoke<'a> {
     }
 }
 
-impl Invoke<'_> {
-    pub const LOG_COST_CALLER: i32 = 0;
-    pub const LOG_COST_INNER: i32 = 0;
-    pub fn new(name: &'_ str, depth: u32) -> Invoke<'_> {
+impl<'a> Invoke<'a> {
+    pub fn new(name: impl Into<Cow<'a, str>>, depth: u32) -> Invoke<'a> {
         Invoke {
-            id: name,
+            id: name.into(),
+            name: None,
+            instruction: None,
             depth,
             consumption: 0,
+            budget: 0,
+            log_cost: Calibration::default().invoke,
+            cpi_cost: Calibration::default().cpi_cost(depth),
+            outcome: None,
             children: vec![],
+            pruned: vec![],
         }
     }
 
@@ -54,48 +147,82 @@ impl Invoke<'_> {
             || line.contains(format!("Program {} failed", self.id).as_str())
     }
 
-    pub fn is_end(&self, lines: &[&str]) -> bool {
-        (!lines.is_empty() && self.is_end_line(lines[0]))
-            || (lines.len() >= 2 && self.is_end_line(lines[1]))
+    /// Moves a leading `Program log: Instruction: <name>` child, as logged by SPL
+    /// and Anchor programs, into `instruction`.
+    pub fn lift_instruction(&mut self) {
+        let instruction = match self.children.first() {
+            Some(InnerLog::Message(message)) => match message.message.strip_prefix(INSTRUCTION) {
+                Some(instruction) => instruction.to_string(),
+                None => return,
+            },
+            _ => return,
+        };
+        self.children.remove(0);
+        self.instruction = Some(instruction);
     }
 
-    pub fn consume_end_lines<'a>(&mut self, lines: &'a [&'a str]) -> Result<&'a [&'a str], Error> {
-        if self.is_end_line(lines[0]) {
-            return Ok(&lines[1..]);
-        }
-        let captures = RE_CONSUMPTION
-            .captures(lines[0])
-            .ok_or(Error::Invoke(lines[0].to_string()))?;
-        self.consumption = captures.get(2).unwrap().as_str().parse().unwrap();
-        Ok(&lines[2..])
+    /// The program name, or id if it has none, followed by the instruction if known,
+    /// e.g. `Token::Transfer`.
+    pub fn label(&self) -> String {
+        label(&self.id, self.name.as_deref(), self.instruction.as_deref())
     }
 
-    pub fn try_from_slice<'a>(lines: &'a [&'a str]) -> Result<(Invoke<'a>, &'a [&'a str]), Error> {
-        if lines.len() < 2 {
-            return Err(Error::Invoke("Not enough lines".to_string()));
+    /// Known once the invoke logged its `consumed <units> of <budget>` line,
+    /// which builtin programs do not.
+    pub fn usage(&self) -> Option<BudgetUsage> {
+        match self.budget {
+            0 => None,
+            budget => Some(BudgetUsage::new(self.consumption, budget)),
         }
-        let mut invoke = Invoke::try_from(lines[0])?;
+    }
 
-        let mut lines = &lines[1..];
-        while !lines.is_empty() && !invoke.is_end(lines) {
-            let (inner_log, remaining_lines) = InnerLog::from_slice(lines);
-            invoke.children.push(inner_log);
-            lines = remaining_lines;
+    /// The outcome recorded by an end line of this invoke.
+    pub fn parse_outcome(&self, line: &str) -> Option<Outcome> {
+        if !self.is_end_line(line) {
+            return None;
         }
-
-        if lines.is_empty() {
-            return Err(Error::Invoke("No end line".to_string()));
+        let error = match RE_FAILED.captures(line) {
+            Some(captures) if captures[1] == *self.id => captures[2].to_string(),
+            _ => return Some(Outcome::Success),
+        };
+        if error.contains("exceeded CUs meter") || error.contains("Computational budget exceeded") {
+            Some(Outcome::ExceededBudget { error })
+        } else {
+            Some(Outcome::Failed { error })
         }
+    }
 
-        lines = invoke.consume_end_lines(lines)?;
+    /// Whether `line` ends any invoke.
+    pub fn is_any_end_line(line: &str) -> bool {
+        RE_END.is_match(line)
+    }
 
-        Ok((invoke, lines))
+    // Looks like "Program NAME consumed <units> of <budget> compute units"
+    pub fn parse_consumption(line: &str) -> Result<(u32, u32), Error> {
+        let captures = RE_CONSUMPTION
+            .captures(line)
+            .ok_or(Error::Invoke(line.to_string()))?;
+        Ok((
+            captures.get(2).unwrap().as_str().parse().unwrap(),
+            captures.get(3).unwrap().as_str().parse().unwrap(),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::log::Log;
+
+    /// The first log in `lines`, which has to be an invoke, and the number of other
+    /// top-level logs.
+    fn first_invoke<'a>(lines: &'a [&'a str]) -> (Invoke<'a>, usize) {
+        let mut inner_logs = Log::from_slice(lines).inner_logs.into_iter();
+        match inner_logs.next() {
+            Some(InnerLog::Invoke(invoke)) => (invoke, inner_logs.count()),
+            other => panic!("{:?}", other),
+        }
+    }
 
     #[test]
     #[tracing_test::traced_test]
@@ -106,13 +233,22 @@ mod tests {
             "Program EyXkTyKARndnKZqPXAEiP7nXRDqRXhsVXGQNW9cZudXy success",
         ];
 
-        let (invoke, remaining_lines) = Invoke::try_from_slice(SLICE).unwrap();
+        let (invoke, others) = first_invoke(SLICE);
         tracing::debug!("{:?}", invoke);
-        tracing::debug!("{:?}", remaining_lines);
         assert_eq!(invoke.id, "EyXkTyKARndnKZqPXAEiP7nXRDqRXhsVXGQNW9cZudXy");
         assert_eq!(invoke.depth, 1);
         assert_eq!(invoke.consumption, 3772);
-        assert_eq!(remaining_lines.len(), 0);
+        assert_eq!(invoke.budget, 200000);
+        assert_eq!(
+            invoke.usage(),
+            Some(BudgetUsage {
+                consumed: 3772,
+                budget: 200000,
+                headroom: 196228,
+                percent_of_budget: 1.886,
+            })
+        );
+        assert_eq!(others, 0);
 
         const SLICE_WITH_GARBAGE: &[&str] = &[
             "Program EyXkTyKARndnKZqPXAEiP7nXRDqRXhsVXGQNW9cZudXy invoke [1]",
@@ -121,11 +257,11 @@ mod tests {
             "Program EyXkTyKARndnKZqPXAEiP7nXRDqRXhsVXGQNW9cZudXy success",
             "garbage",
         ];
-        let (invoke, remaining_lines) = Invoke::try_from_slice(SLICE_WITH_GARBAGE).unwrap();
+        let (invoke, others) = first_invoke(SLICE_WITH_GARBAGE);
         assert_eq!(invoke.id, "EyXkTyKARndnKZqPXAEiP7nXRDqRXhsVXGQNW9cZudXy");
         assert_eq!(invoke.depth, 1);
         assert_eq!(invoke.consumption, 3772);
-        assert_eq!(remaining_lines.len(), 1);
+        assert_eq!(others, 1);
 
         const SLICE_WITH_CHILDREN: &[&str] = &[
             "Program EyXkTyKARndnKZqPXAEiP7nXRDqRXhsVXGQNW9cZudXy invoke [1]",
@@ -141,12 +277,53 @@ mod tests {
             "Program EyXkTyKARndnKZqPXAEiP7nXRDqRXhsVXGQNW9cZudXy success",
         ];
 
-        let (invoke, remaining_lines) = Invoke::try_from_slice(SLICE_WITH_CHILDREN).unwrap();
+        let (invoke, others) = first_invoke(SLICE_WITH_CHILDREN);
         assert_eq!(invoke.id, "EyXkTyKARndnKZqPXAEiP7nXRDqRXhsVXGQNW9cZudXy");
         assert_eq!(invoke.depth, 1);
         assert_eq!(invoke.consumption, 3772);
-        assert_eq!(remaining_lines.len(), 0);
+        assert_eq!(others, 0);
         assert_eq!(invoke.children.len(), 2);
+
+        const SLICE_WITH_INSTRUCTION: &[&str] = &[
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [2]",
+            "Program log: Instruction: Transfer",
+            "Program log: Instruction: Not lifted",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA consumed 4736 of 168220 compute units",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success",
+        ];
+        let (mut invoke, _) = first_invoke(SLICE_WITH_INSTRUCTION);
+        assert_eq!(invoke.instruction.as_deref(), Some("Transfer"));
+        assert_eq!(invoke.children.len(), 1);
+        assert_eq!(
+            invoke.label(),
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA::Transfer"
+        );
+        invoke.name = Some("Token".to_string());
+        assert_eq!(invoke.label(), "Token::Transfer");
+    }
+
+    #[test]
+    fn test_parse_outcome() {
+        let invoke = Invoke::new("SOME_PROGRAM", 1);
+        assert_eq!(
+            invoke.parse_outcome("Program SOME_PROGRAM success"),
+            Some(Outcome::Success)
+        );
+        assert_eq!(
+            invoke.parse_outcome("Program SOME_PROGRAM failed: custom program error: 0x1"),
+            Some(Outcome::Failed {
+                error: "custom program error: 0x1".to_string()
+            })
+        );
+        assert_eq!(
+            invoke.parse_outcome(
+                "Program SOME_PROGRAM failed: exceeded CUs meter at BPF instruction #1234"
+            ),
+            Some(Outcome::ExceededBudget {
+                error: "exceeded CUs meter at BPF instruction #1234".to_string()
+            })
+        );
+        assert_eq!(invoke.parse_outcome("Program OTHER success"), None);
     }
 
     #[test]
@@ -156,15 +333,9 @@ mod tests {
             "Program 11111111111111111111111111111111 invoke [2]",
             "Program 11111111111111111111111111111111 success",
         ];
-        match Invoke::try_from_slice(SLICE) {
-            Ok((invoke, remaining_lines)) => {
-                tracing::debug!("{:?}", invoke);
-                tracing::debug!("{:?}", remaining_lines);
-            }
-            Err(e) => {
-                tracing::error!("{}", e);
-                panic!();
-            }
-        }
+        let (invoke, others) = first_invoke(SLICE);
+        tracing::debug!("{:?}", invoke);
+        assert_eq!(invoke.outcome, Some(Outcome::Success));
+        assert_eq!(others, 0);
     }
 }
@@ -1,9 +1,15 @@
 extern crate serde;
+use std::borrow::Cow;
+
 use self::serde::{Deserialize, Serialize};
 use super::{
     consumption::{Consumer, Report},
+    diagnostic::{self, Diagnostic},
     function::Function,
-    invoke::Invoke,
+    invoke::{BudgetUsage, Invoke},
+    payload::{Data, Marker, Message, Return},
+    stream::StreamParser,
+    transaction::Transaction,
 };
 
 #[derive(Debug, Deserialize)]
@@ -13,6 +19,10 @@ pub enum InnerLog<'a> {
     #[serde(borrow)]
     Invoke(Invoke<'a>),
     Function(Function<'a>),
+    Return(Return<'a>),
+    Data(Data),
+    Message(Message<'a>),
+    Marker(Marker<'a>),
     Unknown(Unknown<'a>),
 }
 
@@ -22,6 +32,10 @@ pub enum InnerLog<'a> {
 pub enum InnerLogWrapper<'a> {
     Invoke(&'a Invoke<'a>),
     Function(&'a Function<'a>),
+    Return(&'a Return<'a>),
+    Data(&'a Data),
+    Message(&'a Message<'a>),
+    Marker(&'a Marker<'a>),
     Unknown(&'a Unknown<'a>),
 }
 
@@ -30,6 +44,10 @@ impl<'a> From<&'a InnerLog<'a>> for InnerLogWrapper<'a> {
         match inner_log {
             InnerLog::Invoke(invoke) => InnerLogWrapper::Invoke(invoke),
             InnerLog::Function(function) => InnerLogWrapper::Function(function),
+            InnerLog::Return(ret) => InnerLogWrapper::Return(ret),
+            InnerLog::Data(data) => InnerLogWrapper::Data(data),
+            InnerLog::Message(message) => InnerLogWrapper::Message(message),
+            InnerLog::Marker(marker) => InnerLogWrapper::Marker(marker),
             InnerLog::Unknown(unknown) => InnerLogWrapper::Unknown(unknown),
         }
     }
@@ -44,6 +62,9 @@ pub struct InnerLogWithReport<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(flatten)]
     pub report: Option<Report>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(flatten)]
+    pub usage: Option<BudgetUsage>,
 }
 
 impl<'a> From<&'a InnerLog<'a>> for InnerLogWithReport<'a> {
@@ -51,6 +72,10 @@ impl<'a> From<&'a InnerLog<'a>> for InnerLogWithReport<'a> {
         InnerLogWithReport {
             inner_log: InnerLogWrapper::from(inner_log),
             report: Some(Report::from(inner_log as &dyn Consumer)),
+            usage: match inner_log {
+                InnerLog::Invoke(invoke) => invoke.usage(),
+                _ => None,
+            },
         }
     }
 }
@@ -66,35 +91,53 @@ impl<'a> Serialize for InnerLog<'a> {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Unknown<'a> {
-    pub line: &'a str,
+    #[serde(borrow)]
+    pub line: Cow<'a, str>,
+    /// Set when the line is where the parser recovered from a malformed log.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostic: Option<Diagnostic>,
 }
 
 impl<'a> Unknown<'a> {
-    pub fn new(line: &'a str) -> Unknown<'a> {
-        Unknown { line }
+    pub fn new(line: impl Into<Cow<'a, str>>) -> Unknown<'a> {
+        Unknown {
+            line: line.into(),
+            diagnostic: None,
+        }
+    }
+
+    pub fn with_diagnostic(line: impl Into<Cow<'a, str>>, diagnostic: Diagnostic) -> Unknown<'a> {
+        Unknown {
+            line: line.into(),
+            diagnostic: Some(diagnostic),
+        }
     }
 }
 
 impl<'a> InnerLog<'a> {
-    pub fn from_slice(lines: &'a [&'a str]) -> (InnerLog<'a>, &'a [&'a str]) {
-        match Function::try_from_slice(lines) {
-            Ok((compute, remaining_lines)) => {
-                return (InnerLog::Function(compute), remaining_lines);
-            }
-            Err(e) => {
-                tracing::trace!("function error {:?}", e);
-            }
+    /// The function name or program id.
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            InnerLog::Function(function) => Some(&function.id),
+            InnerLog::Invoke(invoke) => Some(&invoke.id),
+            _ => None,
         }
-        match Invoke::try_from_slice(lines) {
-            Ok((invoke, remaining_lines)) => {
-                return (InnerLog::Invoke(invoke), remaining_lines);
-            }
-            Err(e) => {
-                tracing::trace!("invoke error {:?}", e);
-            }
+    }
+
+    pub fn inner_logs(&self) -> &[InnerLog<'a>] {
+        match self {
+            InnerLog::Function(function) => &function.children,
+            InnerLog::Invoke(invoke) => &invoke.children,
+            _ => &[],
+        }
+    }
+
+    pub fn inner_logs_mut(&mut self) -> &mut [InnerLog<'a>] {
+        match self {
+            InnerLog::Function(function) => &mut function.children,
+            InnerLog::Invoke(invoke) => &mut invoke.children,
+            _ => &mut [],
         }
-        tracing::trace!("inner log unknown: {:?}", lines[0]);
-        (InnerLog::Unknown(Unknown::new(lines[0])), &lines[1..])
     }
 }
 
@@ -118,17 +161,43 @@ impl<'a> Log<'a> {
         Log { inner_logs: vec![] }
     }
 
-    pub fn from_slice(mut lines: &'a [&'a str]) -> Log<'a> {
-        let mut log = Log::new();
-        loop {
-            if lines.is_empty() {
-                break;
-            }
-            let (inner_log, remaining_lines) = InnerLog::from_slice(lines);
-            log.inner_logs.push(inner_log);
-            lines = remaining_lines;
+    pub fn from_slice(lines: &'a [&'a str]) -> Log<'a> {
+        Log::from_lines(lines.iter().copied())
+    }
+
+    /// The budget usage of all top-level invokes together.
+    ///
+    /// Each top-level invoke is allotted what is left of the transaction's budget,
+    /// so the largest allotment is the budget of the transaction.
+    pub fn usage(&self) -> Option<BudgetUsage> {
+        let usages = self
+            .inner_logs
+            .iter()
+            .filter_map(|inner_log| match inner_log {
+                InnerLog::Invoke(invoke) => invoke.usage(),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let budget = usages.iter().map(|usage| usage.budget).max()?;
+        Some(BudgetUsage::new(
+            usages.iter().map(|usage| usage.consumed).sum(),
+            budget,
+        ))
+    }
+
+    pub fn from_lines<I, S>(lines: I) -> Log<'a>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<Cow<'a, str>>,
+    {
+        let mut parser = StreamParser::new();
+        for line in lines {
+            parser.push(line);
+        }
+        parser.finish();
+        Log {
+            inner_logs: std::iter::from_fn(|| parser.pop()).collect(),
         }
-        log
     }
 }
 
@@ -138,9 +207,40 @@ impl<'a> Default for Log<'a> {
     }
 }
 
+/// A top-level entry of the parsed output.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Entry<'a> {
+    InnerLog(InnerLog<'a>),
+    Transaction(Transaction<'a>),
+}
+
+impl<'a> Entry<'a> {
+    pub fn inner_logs(&self) -> &[InnerLog<'a>] {
+        match self {
+            Entry::InnerLog(inner_log) => std::slice::from_ref(inner_log),
+            Entry::Transaction(transaction) => &transaction.log.inner_logs,
+        }
+    }
+
+    pub fn inner_logs_mut(&mut self) -> &mut [InnerLog<'a>] {
+        match self {
+            Entry::InnerLog(inner_log) => std::slice::from_mut(inner_log),
+            Entry::Transaction(transaction) => &mut transaction.log.inner_logs,
+        }
+    }
+
+    pub fn diagnostics(&self) -> Vec<&Diagnostic> {
+        let mut diagnostics = vec![];
+        diagnostic::collect(self.inner_logs(), &mut diagnostics);
+        diagnostics
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+
     const INPUT: &[&str] = &[
       "Program CTir9Q39BN9seZcSAuHkA4i7gLCaCxyXjP7Ch6KxLEBf invoke [1]",
           "Program log: process_instruction {{",
@@ -216,5 +316,19 @@ mod tests {
         let log = Log::from_slice(INPUT);
         tracing::debug!("{:?}", log);
         assert_eq!(log.inner_logs.len(), 1);
+
+        // `SomeEvent {{ ... }}` is logged by `fn_six`, not the start of a function.
+        let entry = Entry::InnerLog(log.inner_logs.into_iter().next().unwrap());
+        assert!(entry.diagnostics().is_empty());
+        let fn_six = &entry.inner_logs()[0].inner_logs()[0].inner_logs()[1].inner_logs()[4];
+        assert_eq!(fn_six.id(), Some("fn_six"));
+        assert_eq!(
+            fn_six
+                .inner_logs()
+                .iter()
+                .filter_map(|inner_log| inner_log.id())
+                .collect::<Vec<_>>(),
+            vec!["fn_three", "fn_four", "fn_five"]
+        );
     }
 }
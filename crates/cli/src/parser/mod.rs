@@ -1,16 +1,51 @@
 extern crate ansi_term;
 extern crate rayon;
-use self::ansi_term::Colour::{Cyan, Green, Red};
+extern crate serde;
+use std::io::{BufReader, BufWriter, Write as _};
+
+use self::aggregate::Aggregate;
+use self::ansi_term::Colour::{Cyan, Green, Red, Yellow};
+use self::calibration::Calibration;
 pub use self::error::Error;
+use self::filter::Filter;
+use self::folded::{Folded, Weight};
+use self::frames::Frames;
+use self::idl::Events;
+use self::log::Entry;
+use self::output::Format;
+use self::programs::Programs;
 use self::rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+use self::serde::ser::{SerializeSeq as _, Serializer as _};
+use self::top::{Sort, Top};
+use self::trace::Timeline;
+use self::tree::Tree;
 
 use clap::Parser;
 
-mod consumption;
+pub mod aggregate;
+pub mod calibration;
+pub mod consumption;
+pub mod diagnostic;
 pub mod error;
-mod function;
-mod invoke;
-mod log;
+pub mod filter;
+#[cfg(test)]
+mod fixtures;
+pub mod folded;
+pub mod frames;
+pub mod function;
+pub mod idl;
+pub mod input;
+pub mod invoke;
+pub mod log;
+pub mod output;
+pub mod parsed;
+pub mod payload;
+pub mod programs;
+pub mod stream;
+pub mod top;
+pub mod trace;
+pub mod transaction;
+pub mod tree;
 
 #[derive(Parser)]
 pub struct Cli {
@@ -32,6 +67,70 @@ pub struct Args {
     #[clap(short, long, default_value = "_parsed")]
     pub postfix: String,
 
+    /// Format of the input files.
+    #[clap(short, long, value_enum, default_value_t = input::Input::Auto)]
+    pub input: input::Input,
+
+    /// Format of the output files.
+    #[clap(short, long, value_enum, default_value_t = Format::Json)]
+    pub format: Format,
+
+    /// Measurement used as the weight of a frame in folded and svg output.
+    #[clap(short, long, value_enum, default_value_t = Weight::Local)]
+    pub weight: Weight,
+
+    /// Merge all inputs into one report of per-function and per-call-path statistics.
+    #[clap(short, long)]
+    pub aggregate: bool,
+
+    /// Show unknown log lines in tree output instead of collapsing them.
+    #[clap(short, long)]
+    pub verbose: bool,
+
+    /// Fail instead of recovering when the log does not have the expected shape.
+    #[clap(long)]
+    pub strict: bool,
+
+    /// Column the top report is sorted by.
+    #[clap(short, long, value_enum, default_value_t = Sort::SelfCost)]
+    pub sort: Sort,
+
+    /// Anchor IDL, or directory of IDLs, to decode events in `Program data:` lines with.
+    #[clap(long)]
+    pub idl: Vec<String>,
+
+    /// TOML or JSON mapping of program addresses to names, Anchor IDL, or directory
+    /// of these, to label invokes with.
+    #[clap(long)]
+    pub programs: Vec<String>,
+
+    /// Directory written to `$SOL_DEV_FRAMES` by `compute_fn(log = "binary")`, or TOML
+    /// or JSON mapping of frame ids to names, to name frames of binary logs with.
+    #[clap(long)]
+    pub frames: Vec<String>,
+
+    /// Log costs to correct measurements for: a preset runtime version, `auto` to derive
+    /// them from the probe in each log, or a TOML or JSON file with the costs.
+    #[clap(long, default_value = calibration::DEFAULT_PRESET)]
+    pub calibration: String,
+
+    /// Only output frames whose path of names matches, e.g. `fn_seven` or
+    /// `process_instruction/*/fn_seven`, with everything under them.
+    #[clap(long)]
+    pub only: Vec<String>,
+
+    /// Only output invokes of this program, by address or name, or hide them with `!<program>`.
+    #[clap(long)]
+    pub program: Vec<String>,
+
+    /// Hide frames that consume fewer compute units, everything under them included.
+    #[clap(long)]
+    pub min_cu: Option<i32>,
+
+    /// Hide frames nested deeper than this.
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub depth: Option<u32>,
+
     pub path: String,
 }
 
@@ -41,18 +140,143 @@ pub enum Command {
     Dir(Args),
 }
 
+/// Loaded once and applied to every parsed entry.
+struct Annotations {
+    events: Events,
+    programs: Programs,
+    frames: Frames,
+    calibration: Calibration,
+    filter: Filter,
+}
+
+impl Annotations {
+    fn load(args: &Args) -> Result<Annotations, Error> {
+        Ok(Annotations {
+            events: Events::load(&args.idl)?,
+            programs: Programs::load(&args.programs, &args.idl)?,
+            frames: Frames::load(&args.frames)?,
+            calibration: Calibration::load(&args.calibration)?,
+            filter: Filter::new(
+                &args.only,
+                &args.program,
+                args.min_cu,
+                args.depth.map(|depth| depth as usize),
+            )?,
+        })
+    }
+
+    fn apply(&self, entry: &mut Entry) {
+        self.events.decode_entry(entry);
+        self.programs.label_entry(entry);
+        self.frames.label_entry(entry);
+        self.calibration.apply(entry);
+    }
+}
+
+/// Extensions of the input files picked up by `dir`.
+const INPUT_EXTENSIONS: &[&str] = &["json", "log", "txt"];
+
+/// Whether `path` was written by a previous run, its name ending in the postfix.
+fn is_output(path: &std::path::Path, postfix: &str) -> bool {
+    !postfix.is_empty()
+        && path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| stem.ends_with(postfix))
+}
+
 impl Command {
-    fn parse_and_write(&self, infile: &str) -> Result<(), Error> {
+    fn args(&self) -> &Args {
+        match self {
+            Command::File(args) | Command::Dir(args) => args,
+        }
+    }
+
+    /// Parses `infile`, reporting every recovery from a malformed log,
+    /// or failing on the first one with `--strict`.
+    fn parse<F>(&self, infile: &str, annotations: &Annotations, mut emit: F) -> Result<(), Error>
+    where
+        F: FnMut(Entry<'static>) -> Result<(), Error>,
+    {
+        let args = self.args();
+        let reader = BufReader::new(std::fs::File::open(infile)?);
+        args.input.parse(reader, |mut entry| {
+            annotations.apply(&mut entry);
+            for diagnostic in entry.diagnostics() {
+                if args.strict {
+                    return Err(Error::Malformed(diagnostic.clone()));
+                }
+                println!("{} {}: {}", Yellow.paint("Warning"), infile, diagnostic);
+            }
+            for entry in annotations.filter.apply(entry) {
+                emit(entry)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Prints terminal output formats.
+    fn show(&self, infile: &str, annotations: &Annotations) -> Result<(), Error> {
+        let args = self.args();
+        let mut tree = Tree::new(args.verbose);
+        let mut top = Top::new();
+        self.parse(infile, annotations, |entry| {
+            match args.format {
+                Format::Top => top.add_entry(&entry),
+                _ => tree.add_entry(&entry),
+            }
+            Ok(())
+        })?;
+        // Files are parsed in parallel, so print each report in one go.
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", Cyan.paint(infile))?;
+        match args.format {
+            Format::Top => top.write(stdout, args.sort),
+            _ => tree.write(stdout),
+        }
+    }
+
+    fn parse_and_write(&self, infile: &str, annotations: &Annotations) -> Result<(), Error> {
+        let args = self.args();
+        if args.format.is_terminal() {
+            return self.show(infile, annotations);
+        }
         let outfile = self.outfile(infile)?;
-        std::fs::write(
-            &outfile,
-            serde_json::to_string_pretty(&log::Log::from_slice(
-                &serde_json::from_str::<Vec<String>>(&std::fs::read_to_string(infile)?)?
-                    .iter()
-                    .map(|s| s.as_str())
-                    .collect::<Vec<_>>(),
-            ))?,
-        )?;
+        write_output(&outfile, |writer| match args.format {
+            Format::Json => {
+                let mut serializer = serde_json::Serializer::pretty(writer);
+                let mut seq = serializer.serialize_seq(None)?;
+                self.parse(infile, annotations, |entry| {
+                    Ok(seq.serialize_element(&entry)?)
+                })?;
+                Ok(seq.end()?)
+            }
+            Format::Folded | Format::Svg => {
+                let mut folded = Folded::new(args.weight);
+                self.parse(infile, annotations, |entry| {
+                    folded.add_entry(&entry);
+                    Ok(())
+                })?;
+                match args.format {
+                    Format::Svg => folded.write_svg(writer, infile),
+                    _ => folded.write(writer),
+                }
+            }
+            Format::Speedscope | Format::Chrome => {
+                let mut timeline = Timeline::new();
+                self.parse(infile, annotations, |entry| {
+                    timeline.add_entry(&entry);
+                    Ok(())
+                })?;
+                Ok(match args.format {
+                    Format::Speedscope => {
+                        serde_json::to_writer(writer, &timeline.speedscope(infile))
+                    }
+                    _ => serde_json::to_writer(writer, &timeline.chrome()),
+                }?)
+            }
+            Format::Tree | Format::Top => unreachable!("printed by `show`"),
+        })?;
         println!("{} {}", Green.paint("Wrote"), Cyan.paint(outfile));
         Ok(())
     }
@@ -61,43 +285,309 @@ impl Command {
         match self {
             Command::File(args) => Ok(vec![args.path.clone()]),
             Command::Dir(args) => Ok(std::fs::read_dir(args.path.clone())?
-                .map(|p| p.unwrap().path().to_str().unwrap().to_string())
-                .filter(|p| p.ends_with(".json") && !p.ends_with("_parsed.json"))
+                .map(|p| p.unwrap().path())
+                .filter(|p| {
+                    p.extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| INPUT_EXTENSIONS.contains(&e))
+                        && !is_output(p, &args.postfix)
+                })
+                .map(|p| p.to_str().unwrap().to_string())
                 .collect::<Vec<_>>()),
         }
     }
 
     fn outfile(&self, infile: &str) -> Result<String, Error> {
-        Ok(match self {
-            Command::File(args) => args
-                .output
-                .clone()
-                .unwrap_or_else(|| format!("{}{}.json", infile.replace(".json", ""), args.postfix)),
-            Command::Dir(args) => {
-                if let Some(output) = &args.output {
-                    let input_filename = std::path::Path::new(&infile)
-                        .file_name()
-                        .ok_or_else(|| {
-                            std::io::Error::new(std::io::ErrorKind::Other, "No filename")
-                        })?
-                        .to_str()
-                        .ok_or_else(|| {
-                            std::io::Error::new(std::io::ErrorKind::Other, "Invalid filename")
-                        })?;
-                    format!("{}/{}", output, input_filename)
-                } else {
-                    format!("{}{}.json", infile.replace(".json", ""), args.postfix)
-                }
+        let args = self.args();
+        let outfile = |path: &str| format!("{}{}.{}", path, args.postfix, args.format.extension());
+        Ok(match (self, &args.output) {
+            (Command::File(_), Some(output)) => output.clone(),
+            // `--output` is the directory the parsed files go to.
+            (Command::Dir(_), Some(output)) => {
+                let input_stem = std::path::Path::new(&infile)
+                    .file_stem()
+                    .ok_or_else(|| std::io::Error::other("No filename"))?
+                    .to_str()
+                    .ok_or_else(|| std::io::Error::other("Invalid filename"))?;
+                outfile(&format!("{}/{}", output, input_stem))
             }
+            (_, None) => outfile(&strip_extension(infile)),
         })
     }
 
+    fn aggregate(&self, annotations: &Annotations) -> Result<(), Error> {
+        let (aggregate, failed) = self
+            .files()?
+            .into_par_iter()
+            .map(|filename| {
+                let mut aggregate = Aggregate::new();
+                let parsed = self.parse(&filename, annotations, |entry| {
+                    aggregate.add_entry(&entry);
+                    Ok(())
+                });
+                let failed = parsed.err().inspect(|e| report_error(&filename, e));
+                (aggregate, failed.is_some() as usize)
+            })
+            .reduce(
+                || (Aggregate::new(), 0),
+                |(a, m), (b, n)| (a.merge(b), m + n),
+            );
+        if failed > 0 {
+            return Err(Error::Failed(failed));
+        }
+
+        let report = aggregate.report();
+        report.print();
+        let outfile = match self {
+            Command::File(args) => args.output.clone().unwrap_or_else(|| {
+                format!(
+                    "{}{}_aggregate.json",
+                    strip_extension(&args.path),
+                    args.postfix
+                )
+            }),
+            // `--output` is the directory the parsed files go to.
+            Command::Dir(args) => format!(
+                "{}/aggregate{}.json",
+                args.output.as_deref().unwrap_or(&args.path),
+                args.postfix
+            ),
+        };
+        write_output(&outfile, |writer| {
+            Ok(serde_json::to_writer_pretty(writer, &report)?)
+        })?;
+        println!("{} {}", Green.paint("Wrote"), Cyan.paint(outfile));
+        Ok(())
+    }
+
     pub fn run(&self) -> Result<(), Error> {
-        self.files()?.into_par_iter().for_each(|filename| {
-            if let Err(e) = self.parse_and_write(&filename) {
-                println!("{} {}: {}", Red.paint("Error"), filename, e);
-            }
+        let annotations = Annotations::load(self.args())?;
+        if self.args().aggregate {
+            return self.aggregate(&annotations);
+        }
+        let failed = self
+            .files()?
+            .into_par_iter()
+            .filter(|filename| {
+                let parsed = self.parse_and_write(filename, &annotations);
+                parsed.inspect_err(|e| report_error(filename, e)).is_err()
+            })
+            .count();
+        match failed {
+            0 => Ok(()),
+            failed => Err(Error::Failed(failed)),
+        }
+    }
+}
+
+/// Prints why `filename` could not be parsed, which fails the run once every file is done.
+fn report_error(filename: &str, e: &Error) {
+    println!("{} {}: {}", Red.paint("Error"), filename, e);
+}
+
+/// Writes `outfile` with `write` through a temporary file next to it, renamed into place
+/// once written, so that a failed parse leaves no empty or partial output behind.
+fn write_output<F>(outfile: &str, write: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut BufWriter<std::fs::File>) -> Result<(), Error>,
+{
+    let path = std::path::Path::new(outfile);
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let temporary = path.with_file_name(format!(".{}.{}", name, std::process::id()));
+    let written = std::fs::File::create(&temporary)
+        .map_err(Error::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            Ok(writer.flush()?)
         });
-        Ok(())
+    match written {
+        Ok(()) => Ok(std::fs::rename(&temporary, path)?),
+        Err(e) => {
+            let _ = std::fs::remove_file(&temporary);
+            Err(e)
+        }
+    }
+}
+
+fn strip_extension(infile: &str) -> String {
+    std::path::Path::new(infile)
+        .with_extension("")
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MALFORMED: &[&str] = &[
+        "Program SOME_PROGRAM invoke [1]",
+        "Program consumption: 199000 units remaining",
+        "Program log: }} // fn_one",
+        "Program SOME_PROGRAM consumed 1000 of 200000 compute units",
+        "Program SOME_PROGRAM success",
+    ];
+
+    /// A directory of its own for a test, removed with everything in it when dropped.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> TempDir {
+            let dir =
+                std::env::temp_dir().join(format!("sol-dev-cli-{}-{}", test, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.0.join(name).to_str().unwrap().to_string()
+        }
+
+        /// The names of the files in the directory, sorted.
+        fn files(&self) -> Vec<String> {
+            let mut files = std::fs::read_dir(&self.0)
+                .unwrap()
+                .map(|file| file.unwrap().file_name().to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Runs `command` with `args` on `path`.
+    fn command(command: &str, args: &[&str], path: &str) -> Result<(), Error> {
+        Command::try_parse_from(["sol-dev-cli", command].iter().chain(args).chain(&[path]))
+            .unwrap()
+            .run()
+    }
+
+    /// Runs `file` with `args` on `contents` written to a file named `name` in `dir`.
+    fn run(dir: &TempDir, name: &str, contents: &str, args: &[&str]) -> Result<(), Error> {
+        let path = dir.path(name);
+        std::fs::write(&path, contents)?;
+        command("file", args, &path)
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_strict() {
+        let dir = TempDir::new("strict");
+        let json = serde_json::to_string(MALFORMED).unwrap();
+        let text = MALFORMED.join("\n");
+        assert!(matches!(
+            run(&dir, "strict.json", &json, &["--strict"]),
+            Err(Error::Failed(1))
+        ));
+        assert!(matches!(
+            run(&dir, "strict.log", &text, &["--strict"]),
+            Err(Error::Failed(1))
+        ));
+        assert!(run(&dir, "lenient.json", &json, &[]).is_ok());
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_undecodable() {
+        let dir = TempDir::new("undecodable");
+        let cut = r#"["Program SOME_PROGRAM invoke [1]", "#;
+        assert!(matches!(
+            run(&dir, "cut.json", cut, &["--strict"]),
+            Err(Error::Failed(1))
+        ));
+        assert!(matches!(
+            run(&dir, "cut.json", cut, &["--aggregate"]),
+            Err(Error::Failed(1))
+        ));
+        assert!(matches!(
+            run(&dir, "cut.json", cut, &[]),
+            Err(Error::Failed(1))
+        ));
+        // Neither the output nor its temporary file is left behind.
+        assert_eq!(dir.files(), vec!["cut.json"]);
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_files() {
+        let dir = TempDir::new("files");
+        let logs = dir.path("logs_parsed");
+        std::fs::create_dir(&logs).unwrap();
+        for name in ["a.log", "a_parsed.json", "parsed_b.json", "c.txt", "c.svg"] {
+            std::fs::write(format!("{}/{}", logs, name), "").unwrap();
+        }
+        let files = |args: &[&str]| {
+            let command = Command::try_parse_from(
+                ["sol-dev-cli", "dir"]
+                    .iter()
+                    .chain(args)
+                    .chain(&[logs.as_str()]),
+            )
+            .unwrap();
+            let mut files = command
+                .files()
+                .unwrap()
+                .into_iter()
+                .map(|file| file.rsplit('/').next().unwrap().to_string())
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        assert_eq!(files(&[]), vec!["a.log", "c.txt", "parsed_b.json"]);
+        assert_eq!(
+            files(&["--postfix", ""]),
+            vec!["a.log", "a_parsed.json", "c.txt", "parsed_b.json"]
+        );
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_dir_output() {
+        let (logs, output) = (TempDir::new("dir-logs"), TempDir::new("dir-output"));
+        std::fs::write(logs.path("a.log"), MALFORMED.join("\n")).unwrap();
+        let json = serde_json::to_string(MALFORMED).unwrap();
+        std::fs::write(logs.path("b.json"), json).unwrap();
+        for format in ["json", "folded", "svg", "speedscope", "chrome"] {
+            let args = ["--output", &output.path(""), "--format", format];
+            command("dir", &args, &logs.path("")).unwrap();
+        }
+        assert_eq!(
+            output.files(),
+            vec![
+                "a_parsed.folded",
+                "a_parsed.json",
+                "a_parsed.speedscope.json",
+                "a_parsed.svg",
+                "a_parsed.trace.json",
+                "b_parsed.folded",
+                "b_parsed.json",
+                "b_parsed.speedscope.json",
+                "b_parsed.svg",
+                "b_parsed.trace.json",
+            ]
+        );
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_aggregate_output() {
+        let dir = TempDir::new("aggregate");
+        let (logs, output) = (dir.path("logs"), dir.path("out"));
+        std::fs::create_dir(&logs).unwrap();
+        std::fs::create_dir(&output).unwrap();
+        std::fs::write(format!("{}/a.log", logs), MALFORMED.join("\n")).unwrap();
+        command("dir", &["--aggregate", "--output", &output], &logs).unwrap();
+        assert!(std::path::Path::new(&output)
+            .join("aggregate_parsed.json")
+            .exists());
     }
 }
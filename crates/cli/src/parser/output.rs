@@ -0,0 +1,36 @@
+/// The format of an output file.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The parsed tree as pretty JSON.
+    Json,
+    /// Folded call stacks, as consumed by `flamegraph.pl` and `inferno`.
+    Folded,
+    /// An SVG flamegraph.
+    Svg,
+    /// A speedscope profile, on a timeline of consumed compute units.
+    Speedscope,
+    /// Chrome trace events, on a timeline of consumed compute units.
+    Chrome,
+    /// An indented, colored tree, printed to the terminal instead of a file.
+    Tree,
+    /// A table of the total cost of every function, printed to the terminal.
+    Top,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Folded => "folded",
+            Format::Svg => "svg",
+            Format::Speedscope => "speedscope.json",
+            Format::Chrome => "trace.json",
+            Format::Tree | Format::Top => "txt",
+        }
+    }
+
+    /// Whether the format is printed to the terminal rather than written to a file.
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Format::Tree | Format::Top)
+    }
+}
@@ -0,0 +1,202 @@
+extern crate serde;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use self::serde::Deserialize;
+
+use super::{consumption::Report, error::Error, invoke};
+
+/// A node read back from a file written by `parse --format json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Node {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub id: Option<String>,
+    /// The program name and instruction of invokes, see `invoke::label`.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub instruction: Option<String>,
+    #[serde(default)]
+    pub children: Vec<Node>,
+    #[serde(flatten)]
+    pub report: Report,
+}
+
+impl Node {
+    /// What the node is called in call paths: its id, or the label of an invoke.
+    pub fn key(&self) -> Option<String> {
+        let id = self.id.as_deref()?;
+        Some(match self.kind.as_str() {
+            "invoke" => invoke::label(id, self.name.as_deref(), self.instruction.as_deref()),
+            _ => id.to_string(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Transaction { logs: Vec<Node> },
+    Node(Node),
+}
+
+/// Reads the top-level nodes of a parsed file, unwrapping transactions.
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<Node>, Error> {
+    let entries: Vec<Entry> =
+        serde_json::from_reader(std::io::BufReader::new(std::fs::File::open(path)?))?;
+    Ok(entries
+        .into_iter()
+        .flat_map(|entry| match entry {
+            Entry::Transaction { logs } => logs,
+            Entry::Node(node) => vec![node],
+        })
+        .collect())
+}
+
+/// The summed measurements of every call made along one call path.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathStats {
+    pub calls: u32,
+    pub report: Report,
+}
+
+/// The call path of a node called `key` under the node at `parent`, see `by_path`.
+pub fn join(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}/{}", parent, key)
+    }
+}
+
+/// Sums the measurements of all functions and invokes by call path,
+/// the `/` separated keys from the top-level node down to the node itself.
+pub fn by_path(nodes: &[Node]) -> BTreeMap<String, PathStats> {
+    fn visit(node: &Node, parent: &str, paths: &mut BTreeMap<String, PathStats>) {
+        let path = match node.key() {
+            Some(key) => join(parent, &key),
+            None => return,
+        };
+        let stats = paths.entry(path.clone()).or_default();
+        stats.calls += 1;
+        stats.report.naive_local += node.report.naive_local;
+        stats.report.naive_global += node.report.naive_global;
+        stats.report.local += node.report.local;
+        stats.report.global += node.report.global;
+        for child in &node.children {
+            visit(child, &path, paths);
+        }
+    }
+
+    let mut paths = BTreeMap::new();
+    for node in nodes {
+        visit(node, "", &mut paths);
+    }
+    paths
+}
+
+/// One of the measurements of a `Report`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Local,
+    Global,
+    NaiveLocal,
+    NaiveGlobal,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [
+        Metric::Local,
+        Metric::Global,
+        Metric::NaiveLocal,
+        Metric::NaiveGlobal,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Local => "local",
+            Metric::Global => "global",
+            Metric::NaiveLocal => "naive_local",
+            Metric::NaiveGlobal => "naive_global",
+        }
+    }
+
+    pub fn of(self, report: &Report) -> i32 {
+        match self {
+            Metric::Local => report.local,
+            Metric::Global => report.global,
+            Metric::NaiveLocal => report.naive_local,
+            Metric::NaiveGlobal => report.naive_global,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{
+        aggregate::Aggregate,
+        log::{Entry, Log},
+    };
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_read_back() {
+        const INPUT: &[&str] = &[
+            "Program SOME_PROGRAM invoke [1]",
+            "Program log: fn_one {{",
+            "Program consumption: 199004 units remaining",
+            "Program consumption: 198660 units remaining",
+            "Program log: }} // fn_one",
+            "Program log: fn_one {{",
+            "Program consumption: 198000 units remaining",
+            "Program consumption: 197000 units remaining",
+            "Program log: }} // fn_one",
+            "Program SOME_PROGRAM consumed 7218 of 200000 compute units",
+            "Program SOME_PROGRAM success",
+        ];
+        let text = serde_json::to_string(&Log::from_slice(INPUT)).unwrap();
+        let nodes: Vec<Node> = serde_json::from_str(&text).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].children.len(), 2);
+
+        let paths = by_path(&nodes);
+        assert_eq!(paths.len(), 2);
+        let fn_one = &paths["SOME_PROGRAM/fn_one"];
+        assert_eq!(fn_one.calls, 2);
+        assert_eq!(fn_one.report.naive_global, 344 + 1000);
+        assert_eq!(Metric::NaiveGlobal.of(&paths["SOME_PROGRAM"].report), 7218);
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_same_paths_as_aggregate() {
+        const INPUT: &[&str] = &[
+            "Program SOME_PROGRAM invoke [1]",
+            "Program log: Instruction: Deposit",
+            "Program log: fn_one {{",
+            "Program consumption: 199004 units remaining",
+            "Program consumption: 198660 units remaining",
+            "Program log: }} // fn_one",
+            "Program SOME_PROGRAM consumed 7218 of 200000 compute units",
+            "Program SOME_PROGRAM success",
+        ];
+        let log = Log::from_slice(INPUT);
+        let nodes: Vec<Node> = serde_json::from_str(&serde_json::to_string(&log).unwrap()).unwrap();
+        let paths = by_path(&nodes).into_keys().collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec!["SOME_PROGRAM::Deposit", "SOME_PROGRAM::Deposit/fn_one"]
+        );
+
+        let mut aggregate = Aggregate::new();
+        aggregate.add_entry(&Entry::InnerLog(log.inner_logs.into_iter().next().unwrap()));
+        assert_eq!(
+            aggregate.report().by_path.into_keys().collect::<Vec<_>>(),
+            paths
+        );
+    }
+}
@@ -0,0 +1,277 @@
+extern crate base64;
+extern crate lazy_static;
+extern crate regex;
+extern crate serde;
+extern crate serde_json;
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+use self::base64::{engine::general_purpose::STANDARD, Engine as _};
+use self::regex::Regex;
+use self::serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use self::serde_json::Value;
+
+use super::{
+    idl::Event,
+    log::{InnerLog, Unknown},
+};
+
+lazy_static::lazy_static! {
+    static ref RE_RETURN: Regex = Regex::new(r"^Program return: (\w+) ?(\S*)$").unwrap();
+    static ref RE_DATA: Regex = Regex::new(r"^Program data:(.*)$").unwrap();
+    static ref RE_MESSAGE: Regex = Regex::new(r"^Program log: (.*)$").unwrap();
+    static ref RE_MARKER: Regex = Regex::new(
+        r"^Program (\w+) (?:(success)|failed: (.*)|consumed (\d+) of (\d+) compute units)$"
+    )
+    .unwrap();
+}
+
+/// Decoded base64 program output, serialized as hex.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bytes(pub Vec<u8>);
+
+impl Bytes {
+    pub fn from_base64(encoded: &str) -> Option<Bytes> {
+        STANDARD.decode(encoded).ok().map(Bytes)
+    }
+
+    pub fn from_hex(encoded: &str) -> Option<Bytes> {
+        if !encoded.len().is_multiple_of(2) {
+            return None;
+        }
+        (0..encoded.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(encoded.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<_>>>()
+            .map(Bytes)
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = Cow::<str>::deserialize(deserializer)?;
+        Bytes::from_hex(&hex).ok_or_else(|| de::Error::custom("invalid hex"))
+    }
+}
+
+/// `Program return: <program id> <base64>`, set with `sol_set_return_data`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Return<'a> {
+    #[serde(borrow)]
+    pub program: Cow<'a, str>,
+    pub data: Bytes,
+}
+
+/// `Program data: <base64> ...`, logged with `sol_log_data`, e.g. Anchor events.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Data {
+    /// One entry per slice passed to `sol_log_data`.
+    pub data: Vec<Bytes>,
+    /// Set when the data is an event of a loaded IDL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<Event>,
+}
+
+/// `Program log: <message>` that does not belong to a measured function.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Message<'a> {
+    #[serde(borrow)]
+    pub message: Cow<'a, str>,
+}
+
+/// A `consumed`, `success` or `failed` line of an invoke that was not needed
+/// to close it, e.g. because the invoke started before the log did.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Marker<'a> {
+    #[serde(borrow)]
+    pub program: Cow<'a, str>,
+    #[serde(flatten)]
+    pub kind: MarkerKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "marker")]
+pub enum MarkerKind {
+    Consumed { consumed: u32, budget: u32 },
+    Success,
+    Failed { error: String },
+}
+
+impl<'a> fmt::Display for Return<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "return {} {}", self.program, self.data)
+    }
+}
+
+impl fmt::Display for Data {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(event) = &self.event {
+            return write!(
+                f,
+                "event {} {}",
+                event.name,
+                Value::from(event.fields.clone())
+            );
+        }
+        f.write_str("data")?;
+        for data in &self.data {
+            write!(f, " {}", data)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for Message<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl<'a> fmt::Display for Marker<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            MarkerKind::Consumed { consumed, budget } => write!(
+                f,
+                "{} consumed {} of {} compute units",
+                self.program, consumed, budget
+            ),
+            MarkerKind::Success => write!(f, "{} success", self.program),
+            MarkerKind::Failed { error } => write!(f, "{} failed: {}", self.program, error),
+        }
+    }
+}
+
+/// The part of `line` in `range`, borrowed if `line` is.
+fn slice<'a>(line: &Cow<'a, str>, range: Range<usize>) -> Cow<'a, str> {
+    match line {
+        Cow::Borrowed(line) => Cow::Borrowed(&line[range]),
+        Cow::Owned(line) => Cow::Owned(line[range].to_string()),
+    }
+}
+
+/// Types a line that is not part of a function or invoke frame.
+/// Lines that are not program output, or whose base64 does not decode, stay `Unknown`.
+pub fn parse(line: Cow<str>) -> InnerLog {
+    if let Some(captures) = RE_RETURN.captures(&line) {
+        if let Some(data) = Bytes::from_base64(&captures[2]) {
+            return InnerLog::Return(Return {
+                program: slice(&line, captures.get(1).unwrap().range()),
+                data,
+            });
+        }
+    } else if let Some(captures) = RE_DATA.captures(&line) {
+        if let Some(data) = captures[1]
+            .split_whitespace()
+            .map(Bytes::from_base64)
+            .collect()
+        {
+            return InnerLog::Data(Data { data, event: None });
+        }
+    } else if let Some(captures) = RE_MESSAGE.captures(&line) {
+        return InnerLog::Message(Message {
+            message: slice(&line, captures.get(1).unwrap().range()),
+        });
+    } else if let Some(captures) = RE_MARKER.captures(&line) {
+        let kind = if captures.get(2).is_some() {
+            MarkerKind::Success
+        } else if let Some(error) = captures.get(3) {
+            MarkerKind::Failed {
+                error: error.as_str().to_string(),
+            }
+        } else {
+            match (captures[4].parse(), captures[5].parse()) {
+                (Ok(consumed), Ok(budget)) => MarkerKind::Consumed { consumed, budget },
+                _ => return InnerLog::Unknown(Unknown::new(line)),
+            }
+        };
+        return InnerLog::Marker(Marker {
+            program: slice(&line, captures.get(1).unwrap().range()),
+            kind,
+        });
+    }
+    InnerLog::Unknown(Unknown::new(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_parse() {
+        match parse("Program return: SOME_PROGRAM AQID".into()) {
+            InnerLog::Return(ret) => {
+                assert_eq!(ret.program, "SOME_PROGRAM");
+                assert_eq!(ret.data, Bytes(vec![1, 2, 3]));
+            }
+            other => panic!("{:?}", other),
+        }
+        match parse("Program data: AQID BA==".into()) {
+            InnerLog::Data(data) => {
+                assert_eq!(data.data, vec![Bytes(vec![1, 2, 3]), Bytes(vec![4])])
+            }
+            other => panic!("{:?}", other),
+        }
+        match parse("Program log: Instruction: Transfer".into()) {
+            InnerLog::Message(message) => assert_eq!(message.message, "Instruction: Transfer"),
+            other => panic!("{:?}", other),
+        }
+        match parse("Program SOME_PROGRAM consumed 3772 of 200000 compute units".into()) {
+            InnerLog::Marker(marker) => assert_eq!(
+                marker.kind,
+                MarkerKind::Consumed {
+                    consumed: 3772,
+                    budget: 200000
+                }
+            ),
+            other => panic!("{:?}", other),
+        }
+        match parse("Program SOME_PROGRAM failed: custom program error: 0x1".into()) {
+            InnerLog::Marker(marker) => assert_eq!(
+                marker.kind,
+                MarkerKind::Failed {
+                    error: "custom program error: 0x1".to_string()
+                }
+            ),
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            parse("Program data: not base64!".into()),
+            InnerLog::Unknown(_)
+        ));
+        assert!(matches!(
+            parse("Program consumption: 199004 units remaining".into()),
+            InnerLog::Unknown(_)
+        ));
+    }
+
+    #[test]
+    fn test_bytes_json() {
+        let data = Data {
+            data: vec![Bytes(vec![0xde, 0xad])],
+            event: None,
+        };
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(json, r#"{"data":["dead"]}"#);
+        let data: Data = serde_json::from_str(&json).unwrap();
+        assert_eq!(data.data, vec![Bytes(vec![0xde, 0xad])]);
+    }
+}
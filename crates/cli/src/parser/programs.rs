@@ -0,0 +1,162 @@
+extern crate serde_json;
+extern crate toml;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::{
+    error::Error,
+    idl,
+    log::{Entry, InnerLog},
+};
+
+/// Well-known programs, by address.
+const BUILTINS: &[(&str, &str)] = &[
+    ("11111111111111111111111111111111", "System"),
+    ("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "Token"),
+    ("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb", "Token-2022"),
+    (
+        "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+        "Associated Token Account",
+    ),
+    (
+        "ComputeBudget111111111111111111111111111111",
+        "Compute Budget",
+    ),
+];
+
+/// Human readable names of programs, used to label invokes.
+///
+/// Starts out with the well-known programs. More are added from mappings of
+/// addresses to names in TOML or JSON, and from Anchor IDLs.
+#[derive(Debug, Clone)]
+pub struct Programs {
+    names: HashMap<String, String>,
+}
+
+impl Programs {
+    pub fn new() -> Programs {
+        Programs {
+            names: BUILTINS
+                .iter()
+                .map(|(address, name)| (address.to_string(), name.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Loads every mapping or IDL in `paths`, and the programs of the IDLs in `idls`
+    /// that have an address. Both are files or directories of them.
+    pub fn load<P: AsRef<Path>, Q: AsRef<Path>>(
+        paths: &[P],
+        idls: &[Q],
+    ) -> Result<Programs, Error> {
+        let mut programs = Programs::new();
+        for path in idl::files(paths, &["json", "toml"])? {
+            programs.load_file(&path)?;
+        }
+        for path in idl::files(idls, &["json"])? {
+            if let Some((address, name)) = idl::program(&std::fs::read_to_string(path)?)? {
+                programs.names.insert(address, name);
+            }
+        }
+        Ok(programs)
+    }
+
+    fn load_file(&mut self, path: &Path) -> Result<(), Error> {
+        let contents = std::fs::read_to_string(path)?;
+        let loaded = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str::<HashMap<String, String>>(&contents)
+                .map(|names| self.names.extend(names))
+                .map_err(|e| e.to_string()),
+            _ => self.add_json(&contents).map_err(|e| match e {
+                Error::Programs(message) => message,
+                e => e.to_string(),
+            }),
+        };
+        loaded.map_err(|e| Error::Programs(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Adds a JSON mapping of addresses to names, or the program of an Anchor IDL.
+    pub fn add_json(&mut self, json: &str) -> Result<(), Error> {
+        if let Ok(names) = serde_json::from_str::<HashMap<String, String>>(json) {
+            self.names.extend(names);
+            return Ok(());
+        }
+        match idl::program(json)? {
+            Some((address, name)) => {
+                self.names.insert(address, name);
+                Ok(())
+            }
+            None => Err(Error::Programs(
+                "neither a mapping nor an IDL with an address".to_string(),
+            )),
+        }
+    }
+
+    pub fn name(&self, address: &str) -> Option<&str> {
+        self.names.get(address).map(String::as_str)
+    }
+
+    /// Sets the name of every invoke in `entry` whose program is known.
+    pub fn label_entry(&self, entry: &mut Entry) {
+        self.label_all(entry.inner_logs_mut());
+    }
+
+    fn label_all(&self, inner_logs: &mut [InnerLog]) {
+        for inner_log in inner_logs {
+            if let InnerLog::Invoke(invoke) = inner_log {
+                invoke.name = self.name(&invoke.id).map(str::to_string);
+            }
+            self.label_all(inner_log.inner_logs_mut());
+        }
+    }
+}
+
+impl Default for Programs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixtures::entry;
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_label_entry() {
+        let mut programs = Programs::new();
+        programs
+            .add_json(r#"{ "SOME_PROGRAM": "some_program" }"#)
+            .unwrap();
+        programs
+            .add_json(r#"{ "address": "OTHER_PROGRAM", "metadata": { "name": "other_program" }, "instructions": [] }"#)
+            .unwrap();
+        assert!(programs.add_json(r#"{ "instructions": [] }"#).is_err());
+
+        let lines = [
+            "Program SOME_PROGRAM invoke [1]",
+            "Program OTHER_PROGRAM invoke [2]",
+            "Program 11111111111111111111111111111111 invoke [3]",
+            "Program 11111111111111111111111111111111 success",
+            "Program OTHER_PROGRAM success",
+            "Program UNKNOWN_PROGRAM invoke [2]",
+            "Program UNKNOWN_PROGRAM success",
+            "Program SOME_PROGRAM consumed 4000 of 200000 compute units",
+            "Program SOME_PROGRAM success",
+        ];
+        let mut entry = entry(&lines);
+        programs.label_entry(&mut entry);
+        let names = |inner_log: &InnerLog| match inner_log {
+            InnerLog::Invoke(invoke) => invoke.name.clone(),
+            other => panic!("{:?}", other),
+        };
+        let some = &entry.inner_logs()[0];
+        let other = &some.inner_logs()[0];
+        assert_eq!(names(some).as_deref(), Some("some_program"));
+        assert_eq!(names(other).as_deref(), Some("other_program"));
+        assert_eq!(names(&other.inner_logs()[0]).as_deref(), Some("System"));
+        assert_eq!(names(&some.inner_logs()[1]), None);
+    }
+}
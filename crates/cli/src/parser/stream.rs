@@ -0,0 +1,839 @@
+extern crate serde;
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, BufRead};
+
+use self::serde::de::{self, DeserializeSeed, SeqAccess, Visitor};
+
+use super::{
+    consumption::Consumer,
+    diagnostic::{Diagnostic, Position, END_OF_INPUT},
+    error::Error,
+    frames::Exit,
+    function::Function,
+    invoke::{Invoke, Outcome, LOG_TRUNCATED},
+    log::{InnerLog, Unknown},
+    payload::{self, Marker, MarkerKind},
+};
+
+/// An open node on the parser stack, together with the raw lines that opened it,
+/// so it can be turned back into `Unknown` lines if it never closes.
+#[derive(Debug)]
+enum Frame<'a> {
+    Function {
+        node: Function<'a>,
+        line: usize,
+        start: Cow<'a, str>,
+        consumption: Cow<'a, str>,
+    },
+    Invoke {
+        node: Invoke<'a>,
+        line: usize,
+        start: Cow<'a, str>,
+    },
+}
+
+impl<'a> Frame<'a> {
+    fn id(&self) -> &str {
+        match self {
+            Frame::Function { node, .. } => &node.id,
+            Frame::Invoke { node, .. } => &node.id,
+        }
+    }
+
+    fn line(&self) -> usize {
+        match self {
+            Frame::Function { line, .. } | Frame::Invoke { line, .. } => *line,
+        }
+    }
+
+    fn is_end_line(&self, line: &str) -> bool {
+        match self {
+            Frame::Function { node, .. } => node.is_end_line(line),
+            Frame::Invoke { node, .. } => node.is_end_line(line),
+        }
+    }
+
+    /// What would have to come next for this frame to close.
+    fn expected(&self) -> String {
+        match self {
+            Frame::Function { node, .. } => format!(
+                "`Program consumption: <units> units remaining` followed by `}}}} // {}`",
+                node.id
+            ),
+            Frame::Invoke { node, .. } => format!("`Program {} success`", node.id),
+        }
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<InnerLog<'a>> {
+        match self {
+            Frame::Function { node, .. } => &mut node.children,
+            Frame::Invoke { node, .. } => &mut node.children,
+        }
+    }
+
+    fn into_inner_log(self) -> InnerLog<'a> {
+        match self {
+            Frame::Function { node, .. } => InnerLog::Function(node),
+            Frame::Invoke { mut node, .. } => {
+                node.lift_instruction();
+                InnerLog::Invoke(node)
+            }
+        }
+    }
+
+    /// Falls back to `Unknown` for the opening line of a node without an end line,
+    /// with its children moved up to the parent level.
+    fn into_unclosed(self, diagnostic: Diagnostic) -> Vec<InnerLog<'a>> {
+        match self {
+            Frame::Function {
+                node,
+                start,
+                consumption,
+                ..
+            } => vec![
+                InnerLog::Unknown(Unknown::with_diagnostic(start, diagnostic)),
+                InnerLog::Unknown(Unknown::new(consumption)),
+            ]
+            .into_iter()
+            .chain(node.children)
+            .collect(),
+            Frame::Invoke { node, start, .. } => std::iter::once(InnerLog::Unknown(
+                Unknown::with_diagnostic(start, diagnostic),
+            ))
+            .chain(node.children)
+            .collect(),
+        }
+    }
+}
+
+fn function_start<'a>(line: &Cow<'a, str>) -> Option<Function<'a>> {
+    match line {
+        Cow::Borrowed(line) => Function::try_from(*line).ok(),
+        Cow::Owned(line) => Function::try_from(line.as_str())
+            .ok()
+            .map(|function| Function::new(function.id.into_owned())),
+    }
+}
+
+fn invoke_start<'a>(line: &Cow<'a, str>) -> Option<Invoke<'a>> {
+    match line {
+        Cow::Borrowed(line) => Invoke::try_from(*line).ok(),
+        Cow::Owned(line) => Invoke::try_from(line.as_str())
+            .ok()
+            .map(|invoke| Invoke::new(invoke.id.into_owned(), invoke.depth)),
+    }
+}
+
+/// Removes the last of `children` if `parse` accepts it.
+fn take_trailing<T>(
+    children: &mut Vec<InnerLog>,
+    parse: impl Fn(&InnerLog) -> Option<T>,
+) -> Option<T> {
+    let parsed = parse(children.last()?)?;
+    children.pop();
+    Some(parsed)
+}
+
+/// The parser behind `Log::from_lines` and every input format.
+///
+/// Lines are pushed one at a time and completed top-level logs become available
+/// through `pop` as soon as they close. Only the currently open nodes and a single
+/// line of lookahead are kept in memory.
+///
+/// Whenever the log does not have the expected shape, e.g. a function start without
+/// a consumption line or a frame that never closes, the parser keeps the line as
+/// `Unknown` with a `Diagnostic` attached and carries on.
+///
+/// Functions still open when their invoke ends or the log is truncated are closed
+/// as aborted, at the last known "units remaining" counter.
+#[derive(Debug)]
+pub struct StreamParser<'a> {
+    stack: Vec<Frame<'a>>,
+    pending: Option<(usize, Cow<'a, str>)>,
+    ready: VecDeque<InnerLog<'a>>,
+    lines: usize,
+    remaining: Option<u32>,
+}
+
+impl<'a> StreamParser<'a> {
+    pub fn new() -> StreamParser<'a> {
+        StreamParser {
+            stack: vec![],
+            pending: None,
+            ready: VecDeque::new(),
+            lines: 0,
+            remaining: None,
+        }
+    }
+
+    /// Parses `line` as line `self.lines` of the input, once the line after it is known.
+    pub fn push(&mut self, line: impl Into<Cow<'a, str>>) {
+        self.lines += 1;
+        let next = match self.pending.take() {
+            Some((number, pending)) => self.step(number, pending, Some(line.into())),
+            None => Some(line.into()),
+        };
+        self.pending = match next.as_deref().and_then(Exit::decode) {
+            Some(exit) => {
+                self.exit(exit);
+                None
+            }
+            None => next.map(|line| (self.lines, line)),
+        };
+    }
+
+    /// Counts a line of the input that is not part of the log, such as the output
+    /// of a test runner, so that diagnostics refer to lines of the input.
+    pub fn skip(&mut self) {
+        self.lines += 1;
+    }
+
+    /// Flushes the lookahead line and unwinds every node that never closed.
+    pub fn finish(&mut self) {
+        if let Some((number, pending)) = self.pending.take() {
+            self.step(number, pending, None);
+        }
+        while !self.stack.is_empty() {
+            self.unwind(END_OF_INPUT);
+        }
+    }
+
+    /// Takes the next completed top-level log, if any.
+    pub fn pop(&mut self) -> Option<InnerLog<'a>> {
+        self.ready.pop_front()
+    }
+
+    /// Handles line `number`, `line`, with `next` as lookahead.
+    /// Returns the lookahead line if it was not consumed.
+    fn step(
+        &mut self,
+        number: usize,
+        line: Cow<'a, str>,
+        next: Option<Cow<'a, str>>,
+    ) -> Option<Cow<'a, str>> {
+        let ends_at_next = next
+            .as_deref()
+            .is_some_and(|next| self.stack.last().is_some_and(|top| top.is_end_line(next)));
+        match self.stack.last_mut() {
+            Some(Frame::Function { node, .. }) if ends_at_next => {
+                if let Ok(consumption) = Function::parse_consumption(&line) {
+                    node.consumption_end = consumption;
+                    self.remaining = Some(consumption);
+                    self.close();
+                    return None;
+                }
+            }
+            Some(Frame::Invoke { node, .. }) if ends_at_next => {
+                if let Ok((consumption, budget)) = Invoke::parse_consumption(&line) {
+                    node.consumption = consumption;
+                    node.budget = budget;
+                    node.outcome = next.as_deref().and_then(|next| node.parse_outcome(next));
+                    self.remaining = Some(budget.saturating_sub(consumption));
+                    self.close();
+                    return None;
+                }
+            }
+            _ => {}
+        }
+        if line == LOG_TRUNCATED && !self.stack.is_empty() {
+            self.truncate();
+            return next;
+        }
+        if let Some(index) = self
+            .stack
+            .iter()
+            .rposition(|frame| frame.is_end_line(&line))
+        {
+            if self.close_at(index, &line) {
+                return next;
+            }
+        }
+
+        let diagnostic = if let Some(mut node) = function_start(&line) {
+            if let Some(Ok(consumption)) = next.as_deref().map(Function::parse_consumption) {
+                node.consumption_start = consumption;
+                self.remaining = Some(consumption);
+                self.stack.push(Frame::Function {
+                    node,
+                    line: number,
+                    start: line,
+                    consumption: next.unwrap(),
+                });
+                return None;
+            }
+            Some(self.diagnostic(
+                number,
+                format!(
+                    "`Program consumption: <units> units remaining` after the start of {}",
+                    node.id
+                ),
+                next.as_deref().unwrap_or(END_OF_INPUT),
+            ))
+        } else if let Some(node) = invoke_start(&line) {
+            if next.is_some() {
+                self.stack.push(Frame::Invoke {
+                    node,
+                    line: number,
+                    start: line,
+                });
+                return next;
+            }
+            Some(self.diagnostic(
+                number,
+                format!("`Program {} success`", node.id),
+                END_OF_INPUT,
+            ))
+        } else if Function::is_any_end_line(&line) || Invoke::is_any_end_line(&line) {
+            let expected = match self.stack.last() {
+                Some(top) => top.expected(),
+                None => "the end of an open function or invoke".to_string(),
+            };
+            Some(self.diagnostic(number, expected, &line))
+        } else {
+            None
+        };
+
+        let inner_log = match diagnostic {
+            Some(diagnostic) => InnerLog::Unknown(Unknown::with_diagnostic(line, diagnostic)),
+            None => payload::parse(line),
+        };
+        tracing::trace!("inner log payload: {:?}", inner_log);
+        self.attach(inner_log);
+        next
+    }
+
+    /// Closes the frame at `index` on its end line.
+    /// Functions above an ending invoke are aborted, other frames above it are unwound.
+    /// Returns false if a function has no consumption line to close with.
+    fn close_at(&mut self, index: usize, line: &str) -> bool {
+        let outcome = match &self.stack[index] {
+            Frame::Invoke { node, .. } => node.parse_outcome(line),
+            Frame::Function { .. } => None,
+        };
+        match outcome {
+            None => {
+                while self.stack.len() > index + 1 {
+                    self.unwind(line);
+                }
+                let node = match self.stack.last_mut() {
+                    Some(Frame::Function { node, .. }) => node,
+                    _ => return false,
+                };
+                let consumption = take_trailing(&mut node.children, |inner_log| match inner_log {
+                    InnerLog::Unknown(unknown) => Function::parse_consumption(&unknown.line).ok(),
+                    _ => None,
+                });
+                match consumption {
+                    Some(consumption) => {
+                        node.consumption_end = consumption;
+                        self.remaining = Some(consumption);
+                    }
+                    None => return false,
+                }
+            }
+            Some(outcome) => {
+                // The consumed line comes right before the end line, in the innermost frame.
+                let id = self.stack[index].id().to_string();
+                let trailing = self.stack.last_mut().and_then(|top| {
+                    take_trailing(top.children_mut(), |inner_log| match inner_log {
+                        InnerLog::Marker(Marker {
+                            program,
+                            kind: MarkerKind::Consumed { consumed, budget },
+                        }) if *program == id => Some((*consumed, *budget)),
+                        _ => None,
+                    })
+                });
+                let remaining = trailing
+                    .map(|(consumption, budget)| budget.saturating_sub(consumption))
+                    .or(self.remaining);
+                while self.stack.len() > index + 1 {
+                    self.abort(remaining, line);
+                }
+                if let Some(Frame::Invoke { node, .. }) = self.stack.last_mut() {
+                    if let Some((consumption, budget)) = trailing {
+                        node.consumption = consumption;
+                        node.budget = budget;
+                    }
+                    node.outcome = Some(outcome);
+                }
+                self.remaining = remaining;
+            }
+        }
+        self.close();
+        true
+    }
+
+    /// Closes the top function although it never logged its end, because its invoke
+    /// ended first. Other frames are unwound.
+    fn abort(&mut self, remaining: Option<u32>, found: &str) {
+        match self.stack.pop() {
+            Some(Frame::Function { mut node, .. }) => {
+                node.aborted = true;
+                node.consumption_end = remaining.map_or(node.consumption_start, |remaining| {
+                    remaining.min(node.consumption_start)
+                });
+                tracing::debug!("aborted {:?}", node);
+                self.attach(InnerLog::Function(node));
+            }
+            Some(frame) => {
+                self.stack.push(frame);
+                self.unwind(found);
+            }
+            None => {}
+        }
+    }
+
+    /// Closes every open frame, as the runtime stopped recording logs.
+    fn truncate(&mut self) {
+        let remaining = self.remaining;
+        while let Some(top) = self.stack.last_mut() {
+            match top {
+                Frame::Invoke { node, .. } => {
+                    node.outcome = Some(Outcome::Truncated);
+                    // Only the cost of what was logged is known.
+                    node.consumption = node.children_naive_global().max(0) as u32;
+                    self.close();
+                }
+                Frame::Function { .. } => self.abort(remaining, LOG_TRUNCATED),
+            }
+        }
+    }
+
+    /// Turns the top frame back into `Unknown` lines, because `found` came
+    /// instead of its end.
+    fn unwind(&mut self, found: &str) {
+        let diagnostic = match self.stack.last() {
+            Some(top) => Diagnostic {
+                position: Position::Line(top.line()),
+                expected: top.expected(),
+                found: found.to_string(),
+                frame: self.path(),
+            },
+            None => return,
+        };
+        let frame = self.stack.pop().unwrap();
+        tracing::debug!("unclosed {:?}", frame);
+        for inner_log in frame.into_unclosed(diagnostic) {
+            self.attach(inner_log);
+        }
+    }
+
+    fn diagnostic(&self, line: usize, expected: String, found: &str) -> Diagnostic {
+        Diagnostic {
+            position: Position::Line(line),
+            expected,
+            found: found.to_string(),
+            frame: self.path(),
+        }
+    }
+
+    /// The ids of the open frames, outermost first.
+    fn path(&self) -> Option<String> {
+        match self.stack.is_empty() {
+            true => None,
+            false => Some(
+                self.stack
+                    .iter()
+                    .map(Frame::id)
+                    .collect::<Vec<_>>()
+                    .join("/"),
+            ),
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(frame) = self.stack.pop() {
+            let inner_log = frame.into_inner_log();
+            tracing::debug!("closed {:?}", inner_log);
+            self.attach(inner_log);
+        }
+        if self.stack.is_empty() {
+            self.remaining = None;
+        }
+    }
+
+    /// Ends a frame of a binary log, which only logs its end, see `Exit::nest`.
+    fn exit(&mut self, exit: Exit) {
+        match self.stack.last_mut() {
+            Some(frame) => exit.nest(frame.children_mut()),
+            None => self.attach(InnerLog::Function(exit.function())),
+        }
+        self.remaining = Some(exit.end);
+    }
+
+    fn attach(&mut self, inner_log: InnerLog<'a>) {
+        match self.stack.last_mut() {
+            Some(frame) => frame.children_mut().push(inner_log),
+            None => self.ready.push_back(inner_log),
+        }
+    }
+}
+
+impl<'a> Default for StreamParser<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator over the completed top-level logs of a fallible line source.
+pub struct LogStream<'a, I> {
+    lines: I,
+    parser: StreamParser<'a>,
+    finished: bool,
+}
+
+impl<'a, I> LogStream<'a, I> {
+    pub fn new(lines: I) -> LogStream<'a, I> {
+        LogStream {
+            lines,
+            parser: StreamParser::new(),
+            finished: false,
+        }
+    }
+}
+
+impl<R: BufRead> LogStream<'static, io::Lines<R>> {
+    pub fn from_reader(reader: R) -> Self {
+        Self::new(reader.lines())
+    }
+}
+
+impl<'a, I, S, E> Iterator for LogStream<'a, I>
+where
+    I: Iterator<Item = Result<S, E>>,
+    S: Into<Cow<'a, str>>,
+    Error: From<E>,
+{
+    type Item = Result<InnerLog<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(inner_log) = self.parser.pop() {
+                return Some(Ok(inner_log));
+            }
+            if self.finished {
+                return None;
+            }
+            match self.lines.next() {
+                Some(Ok(line)) => self.parser.push(line),
+                Some(Err(e)) => return Some(Err(e.into())),
+                None => {
+                    self.parser.finish();
+                    self.finished = true;
+                }
+            }
+        }
+    }
+}
+
+struct JsonArraySeed<'p, F> {
+    parser: &'p mut StreamParser<'static>,
+    emit: F,
+    /// The error `emit` failed with, which serde can only carry as a message.
+    error: &'p mut Option<Error>,
+}
+
+impl<'de, 'p, F> DeserializeSeed<'de> for JsonArraySeed<'p, F>
+where
+    F: FnMut(InnerLog<'static>) -> Result<(), Error>,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'p, F> Visitor<'de> for JsonArraySeed<'p, F>
+where
+    F: FnMut(InnerLog<'static>) -> Result<(), Error>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of log lines")
+    }
+
+    fn visit_seq<A>(mut self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while let Some(line) = seq.next_element::<String>()? {
+            self.parser.push(line);
+            while let Some(inner_log) = self.parser.pop() {
+                if let Err(e) = (self.emit)(inner_log) {
+                    let message = e.to_string();
+                    *self.error = Some(e);
+                    return Err(de::Error::custom(message));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a JSON array of log lines without collecting it first,
+/// handing every completed top-level log to `emit`.
+pub fn parse_json_array<R, F>(reader: R, mut emit: F) -> Result<(), Error>
+where
+    R: io::Read,
+    F: FnMut(InnerLog<'static>) -> Result<(), Error>,
+{
+    let mut parser = StreamParser::new();
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let mut error = None;
+    let deserialized = JsonArraySeed {
+        parser: &mut parser,
+        emit: &mut emit,
+        error: &mut error,
+    }
+    .deserialize(&mut deserializer);
+    if let Err(e) = deserialized {
+        return Err(error.unwrap_or(e.into()));
+    }
+    deserializer.end()?;
+    parser.finish();
+    while let Some(inner_log) = parser.pop() {
+        emit(inner_log)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::log::{Entry, Log};
+
+    const INPUT: &[&str] = &[
+        "garbage",
+        "Program SOME_PROGRAM invoke [1]",
+        "Program log: fn_one {{",
+        "Program consumption: 199004 units remaining",
+        "Program log: fn_two {{",
+        "Program consumption: 198799 units remaining",
+        "Program 11111111111111111111111111111111 invoke [2]",
+        "Program 11111111111111111111111111111111 success",
+        "Program consumption: 198660 units remaining",
+        "Program log: }} // fn_two",
+        "Program log: not_a_function {{",
+        "Program log: Instruction: Transfer",
+        "Program consumption: 192993 units remaining",
+        "Program log: }} // fn_one",
+        "Program SOME_PROGRAM consumed 7218 of 200000 compute units",
+        "Program SOME_PROGRAM success",
+        "Program OTHER_PROGRAM invoke [1]",
+        "Program OTHER_PROGRAM consumed 100 of 200000 compute units",
+        "Program OTHER_PROGRAM success",
+    ];
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_stream_inputs() {
+        let log = Log::from_slice(INPUT);
+        let ids = |inner_logs: &[InnerLog]| {
+            inner_logs
+                .iter()
+                .map(|inner_log| inner_log.id().map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(&log.inner_logs),
+            vec![
+                None,
+                Some("SOME_PROGRAM".to_string()),
+                Some("OTHER_PROGRAM".to_string())
+            ]
+        );
+        let fn_one = &log.inner_logs[1].inner_logs()[0];
+        assert_eq!(fn_one.id(), Some("fn_one"));
+        // `not_a_function` never ends, so it is kept as an unknown line.
+        assert_eq!(
+            ids(fn_one.inner_logs()),
+            vec![Some("fn_two".to_string()), None, None]
+        );
+        let expected = serde_json::to_value(&log).unwrap();
+
+        let mut parser = StreamParser::new();
+        let mut inner_logs = vec![];
+        for line in INPUT {
+            parser.push(*line);
+            inner_logs.extend(std::iter::from_fn(|| parser.pop()));
+        }
+        parser.finish();
+        inner_logs.extend(std::iter::from_fn(|| parser.pop()));
+        assert_eq!(serde_json::to_value(&inner_logs).unwrap(), expected);
+
+        let reader = INPUT.join("\n");
+        let streamed = LogStream::from_reader(reader.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(serde_json::to_value(&streamed).unwrap(), expected);
+
+        let json = serde_json::to_string(INPUT).unwrap();
+        let mut from_json = vec![];
+        parse_json_array(json.as_bytes(), |inner_log| {
+            from_json.push(inner_log);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(serde_json::to_value(&from_json).unwrap(), expected);
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_stream_emits_closed_invokes() {
+        let mut parser = StreamParser::new();
+        for line in &INPUT[..15] {
+            parser.push(*line);
+        }
+        assert!(matches!(parser.pop(), Some(InnerLog::Unknown(_))));
+        // The consumed line is still the lookahead.
+        assert!(parser.pop().is_none());
+        parser.push(INPUT[15]);
+        assert!(matches!(parser.pop(), Some(InnerLog::Invoke(_))));
+        assert!(parser.pop().is_none());
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_stream_unclosed() {
+        const UNCLOSED: &[&str] = &[
+            "Program SOME_PROGRAM invoke [1]",
+            "Program log: fn_one {{",
+            "Program consumption: 199004 units remaining",
+            "Program log: fn_two {{",
+            "Program consumption: 198799 units remaining",
+            "Program consumption: 198660 units remaining",
+            "Program log: }} // fn_two",
+        ];
+        let streamed = LogStream::new(UNCLOSED.iter().map(|line| Ok::<_, Error>(*line)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        // The unclosed invoke and function fall back to their lines, the closed function stays.
+        assert_eq!(streamed.len(), 4);
+        assert!(streamed[..3]
+            .iter()
+            .all(|inner_log| matches!(inner_log, InnerLog::Unknown(_))));
+        assert_eq!(streamed[3].id(), Some("fn_two"));
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_stream_diagnostics() {
+        let mut lines = INPUT.to_vec();
+        // Drop the consumption line in front of `}} // fn_two` and truncate.
+        lines.remove(8);
+        lines.truncate(12);
+        let streamed = LogStream::new(lines.iter().map(|line| Ok::<_, Error>(*line)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let entries = streamed
+            .into_iter()
+            .map(Entry::InnerLog)
+            .collect::<Vec<_>>();
+        let diagnostics = entries
+            .iter()
+            .flat_map(|entry| entry.diagnostics())
+            .map(|diagnostic| {
+                (
+                    diagnostic.position,
+                    diagnostic.found.as_str(),
+                    diagnostic.frame.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (Position::Line(2), "end of input", Some("SOME_PROGRAM")),
+                (
+                    Position::Line(3),
+                    "end of input",
+                    Some("SOME_PROGRAM/fn_one")
+                ),
+                (
+                    Position::Line(5),
+                    "end of input",
+                    Some("SOME_PROGRAM/fn_one/fn_two")
+                ),
+                (
+                    Position::Line(9),
+                    "Program log: }} // fn_two",
+                    Some("SOME_PROGRAM/fn_one/fn_two")
+                ),
+                (
+                    Position::Line(10),
+                    "Program log: Instruction: Transfer",
+                    Some("SOME_PROGRAM/fn_one/fn_two")
+                ),
+            ]
+        );
+        assert_eq!(
+            entries[1].diagnostics()[0].to_string(),
+            "line 2: expected `Program SOME_PROGRAM success`, found `end of input`".to_string()
+                + " in SOME_PROGRAM"
+        );
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_stream_outcomes() {
+        const FAILED: &[&str] = &[
+            "Program SOME_PROGRAM invoke [1]",
+            "Program log: fn_one {{",
+            "Program consumption: 199000 units remaining",
+            "Program log: fn_two {{",
+            "Program consumption: 198000 units remaining",
+            "Program log: panicked at 'oops', src/lib.rs:1:1",
+            "Program SOME_PROGRAM consumed 5000 of 200000 compute units",
+            "Program SOME_PROGRAM failed: SBF program panicked",
+            "Program OTHER_PROGRAM invoke [1]",
+            "Program log: fn_one {{",
+            "Program consumption: 194000 units remaining",
+            "Log truncated",
+        ];
+        let inner_logs = Log::from_slice(FAILED).inner_logs;
+        assert_eq!(inner_logs.len(), 2);
+        let mut diagnostics = vec![];
+        crate::parser::diagnostic::collect(&inner_logs, &mut diagnostics);
+        assert!(diagnostics.is_empty());
+
+        let failed = match &inner_logs[0] {
+            InnerLog::Invoke(invoke) => invoke,
+            inner_log => panic!("{:?}", inner_log),
+        };
+        assert_eq!(
+            failed.outcome,
+            Some(Outcome::Failed {
+                error: "SBF program panicked".to_string()
+            })
+        );
+        assert_eq!(failed.consumption, 5000);
+        let fn_one = match &failed.children[0] {
+            InnerLog::Function(function) => function,
+            inner_log => panic!("{:?}", inner_log),
+        };
+        assert!(fn_one.aborted);
+        assert_eq!(fn_one.consumption_end, 195000);
+        match &fn_one.children[0] {
+            InnerLog::Function(fn_two) => {
+                assert!(fn_two.aborted);
+                assert_eq!(fn_two.naive_global(), 3000);
+                assert_eq!(fn_two.children.len(), 1);
+            }
+            inner_log => panic!("{:?}", inner_log),
+        }
+
+        let truncated = serde_json::to_value(&inner_logs[1]).unwrap();
+        assert_eq!(truncated["outcome"]["status"], "truncated");
+        assert_eq!(truncated["children"][0]["aborted"], true);
+        assert_eq!(truncated["children"][0]["naive_global"], 0);
+    }
+}
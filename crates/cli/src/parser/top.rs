@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use super::{
+    consumption::Consumer,
+    error::Error,
+    log::{Entry, InnerLog},
+};
+
+/// The column the top report is sorted by, largest first.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    /// Total `local` cost.
+    #[value(name = "self")]
+    SelfCost,
+    /// Total `global` cost.
+    Inclusive,
+    /// Number of calls.
+    Calls,
+    /// Average `local` cost per call.
+    Avg,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Row {
+    pub calls: u32,
+    pub self_cost: i64,
+    pub inclusive: i64,
+}
+
+impl Row {
+    pub fn avg_self(&self) -> f64 {
+        self.self_cost as f64 / self.calls.max(1) as f64
+    }
+
+    pub fn avg_inclusive(&self) -> f64 {
+        self.inclusive as f64 / self.calls.max(1) as f64
+    }
+}
+
+/// A flat report of every function, summed over all of its calls.
+///
+/// The inclusive cost of recursive functions counts nested calls more than once.
+#[derive(Debug, Default)]
+pub struct Top {
+    rows: BTreeMap<String, Row>,
+}
+
+impl Top {
+    pub fn new() -> Top {
+        Top::default()
+    }
+
+    pub fn add_entry(&mut self, entry: &Entry) {
+        for inner_log in entry.inner_logs() {
+            self.add(inner_log);
+        }
+    }
+
+    fn add(&mut self, inner_log: &InnerLog) {
+        if let InnerLog::Function(function) = inner_log {
+            let row = self.rows.entry(function.id.to_string()).or_default();
+            row.calls += 1;
+            row.self_cost += inner_log.local_ex_log() as i64;
+            row.inclusive += inner_log.global_ex_log() as i64;
+        }
+        for child in inner_log.inner_logs() {
+            self.add(child);
+        }
+    }
+
+    pub fn rows(&self, sort: Sort) -> Vec<(&str, &Row)> {
+        let mut rows = self
+            .rows
+            .iter()
+            .map(|(name, row)| (name.as_str(), row))
+            .collect::<Vec<_>>();
+        rows.sort_by(|(_, a), (_, b)| match sort {
+            Sort::SelfCost => b.self_cost.cmp(&a.self_cost),
+            Sort::Inclusive => b.inclusive.cmp(&a.inclusive),
+            Sort::Calls => b.calls.cmp(&a.calls),
+            Sort::Avg => b.avg_self().total_cmp(&a.avg_self()),
+        });
+        rows
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W, sort: Sort) -> Result<(), Error> {
+        writeln!(
+            writer,
+            "{:>10} {:>10} {:>7} {:>10} {:>10}  function",
+            "self", "inclusive", "calls", "avg self", "avg incl"
+        )?;
+        for (name, row) in self.rows(sort) {
+            writeln!(
+                writer,
+                "{:>10} {:>10} {:>7} {:>10.1} {:>10.1}  {}",
+                row.self_cost,
+                row.inclusive,
+                row.calls,
+                row.avg_self(),
+                row.avg_inclusive(),
+                name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixtures::{entries, NESTED};
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_top() {
+        let mut top = Top::new();
+        for entry in entries(NESTED) {
+            top.add_entry(&entry);
+        }
+        let names = |sort| {
+            top.rows(sort)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(Sort::SelfCost), vec!["fn_two", "fn_one"]);
+        assert_eq!(names(Sort::Inclusive), vec!["fn_one", "fn_two"]);
+
+        let rows = top.rows(Sort::Calls);
+        assert_eq!(
+            rows[0],
+            (
+                "fn_two",
+                &Row {
+                    calls: 2,
+                    self_cost: 4000,
+                    inclusive: 4000,
+                }
+            )
+        );
+        assert_eq!(rows[0].1.avg_self(), 2000.0);
+        assert_eq!(rows[1].1.calls, 1);
+        assert_eq!(rows[1].1.self_cost, 7000 - 4000 - 2 * 308);
+    }
+}
@@ -0,0 +1,318 @@
+extern crate serde;
+
+use std::collections::HashMap;
+
+use self::serde::Serialize;
+
+use super::log::{Entry, InnerLog};
+
+/// A node of the parsed tree placed on a timeline of consumed compute units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub name: String,
+    pub start: u64,
+    pub end: u64,
+    pub children: Vec<Span>,
+}
+
+/// Places the parsed tree on a timeline, using the "units remaining" counters as the clock.
+///
+/// Within a top-level invoke with budget `M`, a counter of `r` units remaining maps to
+/// `M - r` units after the start of the invoke. Nested invokes start at their own budget,
+/// which is the number of units remaining when they were called. Top-level invokes follow
+/// each other. Nodes without counters, such as builtin programs that do not log their
+/// consumption, take no time. Children are clamped to their parent so the timeline nests.
+#[derive(Debug, Default)]
+pub struct Timeline {
+    pub spans: Vec<Span>,
+    end: u64,
+}
+
+impl Timeline {
+    pub fn new() -> Timeline {
+        Timeline::default()
+    }
+
+    pub fn add_entry(&mut self, entry: &Entry) {
+        let start = self.end;
+        let mut spans = vec![];
+        let mut now = start;
+        for inner_log in entry.inner_logs() {
+            if let Some(span) = Self::top_level(inner_log, now) {
+                now = span.end;
+                spans.push(span);
+            }
+        }
+        self.end = now;
+        match entry {
+            Entry::Transaction(transaction) => self.spans.push(Span {
+                name: transaction
+                    .signature
+                    .clone()
+                    .unwrap_or_else(|| "transaction".to_string()),
+                start,
+                end: now,
+                children: spans,
+            }),
+            Entry::InnerLog(_) => self.spans.extend(spans),
+        }
+    }
+
+    fn top_level(inner_log: &InnerLog, start: u64) -> Option<Span> {
+        let origin = match inner_log {
+            InnerLog::Invoke(invoke) => start + invoke.budget as u64,
+            _ => start,
+        };
+        let mut now = start;
+        Self::span(inner_log, origin, start, u64::MAX, &mut now)
+    }
+
+    /// `origin` is the time at which zero units would remain.
+    fn span(
+        inner_log: &InnerLog,
+        origin: u64,
+        lower: u64,
+        upper: u64,
+        now: &mut u64,
+    ) -> Option<Span> {
+        let at = |remaining: u32| origin.saturating_sub(remaining as u64);
+        let (start, end) = match inner_log {
+            InnerLog::Function(function) => {
+                (at(function.consumption_start), at(function.consumption_end))
+            }
+            InnerLog::Invoke(invoke) if invoke.budget > 0 => {
+                let start = at(invoke.budget);
+                (start, start + invoke.consumption as u64)
+            }
+            InnerLog::Invoke(_) => (*now, *now),
+            _ => return None,
+        };
+        let start = start.max(*now).max(lower).min(upper);
+        let end = end.max(start).min(upper);
+
+        let mut children = vec![];
+        let mut child_now = start;
+        for child in inner_log.inner_logs() {
+            if let Some(span) = Self::span(child, origin, start, end, &mut child_now) {
+                child_now = span.end;
+                children.push(span);
+            }
+        }
+        *now = end;
+        Some(Span {
+            name: inner_log.id().unwrap_or_default().to_string(),
+            start,
+            end,
+            children,
+        })
+    }
+
+    /// The timeline in speedscope's evented profile format.
+    /// See <https://github.com/jlfwong/speedscope/wiki/Importing-from-custom-sources>.
+    pub fn speedscope(&self, name: &str) -> Speedscope {
+        fn visit(
+            span: &Span,
+            frames: &mut Vec<SpeedscopeFrame>,
+            indices: &mut HashMap<String, usize>,
+            events: &mut Vec<SpeedscopeEvent>,
+        ) {
+            let frame = *indices.entry(span.name.clone()).or_insert_with(|| {
+                frames.push(SpeedscopeFrame {
+                    name: span.name.clone(),
+                });
+                frames.len() - 1
+            });
+            events.push(SpeedscopeEvent {
+                kind: "O",
+                frame,
+                at: span.start,
+            });
+            for child in &span.children {
+                visit(child, frames, indices, events);
+            }
+            events.push(SpeedscopeEvent {
+                kind: "C",
+                frame,
+                at: span.end,
+            });
+        }
+
+        let mut frames = vec![];
+        let mut events = vec![];
+        let mut indices = HashMap::new();
+        for span in &self.spans {
+            visit(span, &mut frames, &mut indices, &mut events);
+        }
+        Speedscope {
+            schema: "https://www.speedscope.app/file-format-schema.json",
+            name: name.to_string(),
+            exporter: "sol-dev-cli",
+            shared: SpeedscopeShared { frames },
+            profiles: vec![SpeedscopeProfile {
+                kind: "evented",
+                name: name.to_string(),
+                unit: "none",
+                start_value: 0,
+                end_value: self.end,
+                events,
+            }],
+        }
+    }
+
+    /// The timeline as Chrome trace events, one complete event per span,
+    /// with one compute unit per microsecond.
+    pub fn chrome(&self) -> Chrome {
+        fn visit(span: &Span, events: &mut Vec<ChromeEvent>) {
+            events.push(ChromeEvent {
+                name: span.name.clone(),
+                phase: "X",
+                ts: span.start,
+                dur: span.end - span.start,
+                pid: 1,
+                tid: 1,
+            });
+            for child in &span.children {
+                visit(child, events);
+            }
+        }
+
+        let mut trace_events = vec![];
+        for span in &self.spans {
+            visit(span, &mut trace_events);
+        }
+        Chrome { trace_events }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Speedscope {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    name: String,
+    exporter: &'static str,
+    shared: SpeedscopeShared,
+    profiles: Vec<SpeedscopeProfile>,
+}
+
+#[derive(Debug, Serialize)]
+struct SpeedscopeShared {
+    frames: Vec<SpeedscopeFrame>,
+}
+
+#[derive(Debug, Serialize)]
+struct SpeedscopeFrame {
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpeedscopeProfile {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: String,
+    unit: &'static str,
+    start_value: u64,
+    end_value: u64,
+    events: Vec<SpeedscopeEvent>,
+}
+
+#[derive(Debug, Serialize)]
+struct SpeedscopeEvent {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    frame: usize,
+    at: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chrome {
+    trace_events: Vec<ChromeEvent>,
+}
+
+#[derive(Debug, Serialize)]
+struct ChromeEvent {
+    name: String,
+    #[serde(rename = "ph")]
+    phase: &'static str,
+    ts: u64,
+    dur: u64,
+    pid: u32,
+    tid: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixtures::entries;
+
+    const INPUT: &[&str] = &[
+        "Program SOME_PROGRAM invoke [1]",
+        "Program log: fn_one {{",
+        "Program consumption: 199000 units remaining",
+        "Program 11111111111111111111111111111111 invoke [2]",
+        "Program 11111111111111111111111111111111 success",
+        "Program OTHER_PROGRAM invoke [2]",
+        "Program OTHER_PROGRAM consumed 1000 of 198000 compute units",
+        "Program OTHER_PROGRAM success",
+        "Program consumption: 196000 units remaining",
+        "Program log: }} // fn_one",
+        "Program SOME_PROGRAM consumed 5000 of 200000 compute units",
+        "Program SOME_PROGRAM success",
+        "Program SOME_PROGRAM invoke [1]",
+        "Program SOME_PROGRAM consumed 300 of 195000 compute units",
+        "Program SOME_PROGRAM success",
+    ];
+
+    fn new_span(name: &str, start: u64, end: u64, children: Vec<Span>) -> Span {
+        Span {
+            name: name.to_string(),
+            start,
+            end,
+            children,
+        }
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_timeline() {
+        let mut timeline = Timeline::new();
+        for entry in entries(INPUT) {
+            timeline.add_entry(&entry);
+        }
+        assert_eq!(
+            timeline.spans,
+            vec![
+                new_span(
+                    "SOME_PROGRAM",
+                    0,
+                    5000,
+                    vec![new_span(
+                        "fn_one",
+                        1000,
+                        4000,
+                        vec![
+                            new_span("11111111111111111111111111111111", 1000, 1000, vec![]),
+                            new_span("OTHER_PROGRAM", 2000, 3000, vec![]),
+                        ]
+                    )]
+                ),
+                new_span("SOME_PROGRAM", 5000, 5300, vec![]),
+            ]
+        );
+
+        let speedscope = serde_json::to_value(timeline.speedscope("test")).unwrap();
+        assert_eq!(speedscope["shared"]["frames"].as_array().unwrap().len(), 4);
+        assert_eq!(speedscope["profiles"][0]["endValue"], 5300);
+        let events = speedscope["profiles"][0]["events"].as_array().unwrap();
+        assert_eq!(events.len(), 10);
+        assert!(events
+            .windows(2)
+            .all(|w| w[0]["at"].as_u64() <= w[1]["at"].as_u64()));
+
+        let chrome = serde_json::to_value(timeline.chrome()).unwrap();
+        assert_eq!(chrome["traceEvents"].as_array().unwrap().len(), 5);
+        assert_eq!(chrome["traceEvents"][1]["ts"], 1000);
+        assert_eq!(chrome["traceEvents"][1]["dur"], 3000);
+    }
+}
@@ -0,0 +1,350 @@
+extern crate serde;
+extern crate serde_json;
+
+use std::fmt;
+use std::io;
+
+use self::serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use self::serde::{Deserialize, Serialize};
+use self::serde_json::Value;
+
+use super::{
+    diagnostic::{Diagnostic, Position},
+    error::Error,
+    invoke::BudgetUsage,
+    log::{InnerLog, Log, Unknown},
+};
+
+/// The logs of a single transaction, along with what identifies it on chain.
+#[derive(Debug, Serialize)]
+pub struct Transaction<'a> {
+    pub signature: Option<String>,
+    pub slot: Option<u64>,
+    pub compute_units_consumed: Option<u64>,
+    /// Rollup of the budget usage of the top-level invokes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<BudgetUsage>,
+    #[serde(rename = "logs")]
+    pub log: Log<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcTransaction {
+    slot: Option<u64>,
+    transaction: Option<Value>,
+    /// Missing for transactions that failed before they were executed.
+    meta: Option<RpcMeta>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcMeta {
+    log_messages: Option<Vec<String>>,
+    compute_units_consumed: Option<u64>,
+}
+
+impl From<RpcTransaction> for Transaction<'static> {
+    fn from(rpc: RpcTransaction) -> Self {
+        // Only json encoded transactions carry readable signatures.
+        let signature = rpc
+            .transaction
+            .as_ref()
+            .and_then(|transaction| transaction.get("signatures"))
+            .and_then(|signatures| signatures.get(0))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let meta = rpc.meta.unwrap_or(RpcMeta {
+            log_messages: None,
+            compute_units_consumed: None,
+        });
+        let log = Log::from_lines(meta.log_messages.unwrap_or_default());
+        Transaction {
+            signature,
+            slot: rpc.slot,
+            compute_units_consumed: meta.compute_units_consumed,
+            usage: log.usage(),
+            log,
+        }
+    }
+}
+
+impl Transaction<'static> {
+    /// Stands in for response `number` of the input, which holds `found` rather than
+    /// a transaction, e.g. a JSON-RPC error. Its log is only the diagnostic.
+    fn missing(number: usize, found: &Value) -> Transaction<'static> {
+        let found = found.to_string();
+        let diagnostic = Diagnostic {
+            position: Position::Response(number),
+            expected: "a transaction".to_string(),
+            found: found.clone(),
+            frame: None,
+        };
+        Transaction {
+            signature: None,
+            slot: None,
+            compute_units_consumed: None,
+            usage: None,
+            log: Log {
+                inner_logs: vec![InnerLog::Unknown(Unknown::with_diagnostic(
+                    found, diagnostic,
+                ))],
+            },
+        }
+    }
+
+    /// Hands every transaction found in response `number` of the input to `emit`.
+    ///
+    /// Accepts `getTransaction` results, JSON-RPC responses wrapping them,
+    /// the output of `solana confirm -v --output json`, and arrays of any of these.
+    /// Responses without a transaction are kept as a diagnostic, see `missing`.
+    fn collect<F>(number: usize, value: Value, emit: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(Transaction<'static>) -> Result<(), Error>,
+    {
+        match value {
+            Value::Array(values) => {
+                for value in values {
+                    Self::collect(number, value, emit)?;
+                }
+            }
+            Value::Object(mut object) => {
+                // A JSON-RPC error, or a `null` result for a transaction that was not found.
+                if let Some(error) = object
+                    .remove("error")
+                    .or_else(|| object.get("result").filter(|r| r.is_null()).cloned())
+                {
+                    emit(Transaction::missing(number, &error))?;
+                } else if let Some(result) = object.remove("result") {
+                    Self::collect(number, result, emit)?;
+                } else if object.contains_key("meta") {
+                    let rpc = serde_json::from_value::<RpcTransaction>(Value::Object(object))?;
+                    emit(rpc.into())?;
+                } else if let Some(transaction) = object.remove("transaction") {
+                    Self::collect(number, transaction, emit)?;
+                } else {
+                    tracing::debug!("no transaction in {:?}", object.keys().collect::<Vec<_>>());
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes the input one response at a time: a single response, or each element
+/// of an array of them, numbered from 1.
+struct ResponsesSeed<'p, F> {
+    emit: F,
+    /// The error `emit` failed with, which serde can only carry as a message.
+    error: &'p mut Option<Error>,
+}
+
+impl<'p, F> ResponsesSeed<'p, F>
+where
+    F: FnMut(Transaction<'static>) -> Result<(), Error>,
+{
+    fn collect<E: de::Error>(&mut self, number: usize, response: Value) -> Result<(), E> {
+        Transaction::collect(number, response, &mut self.emit).map_err(|e| {
+            let message = e.to_string();
+            *self.error = Some(e);
+            de::Error::custom(message)
+        })
+    }
+}
+
+impl<'de, 'p, F> DeserializeSeed<'de> for ResponsesSeed<'p, F>
+where
+    F: FnMut(Transaction<'static>) -> Result<(), Error>,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'p, F> Visitor<'de> for ResponsesSeed<'p, F>
+where
+    F: FnMut(Transaction<'static>) -> Result<(), Error>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an RPC response or an array of them")
+    }
+
+    fn visit_seq<A>(mut self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut number = 0;
+        while let Some(response) = seq.next_element::<Value>()? {
+            number += 1;
+            self.collect(number, response)?;
+        }
+        Ok(())
+    }
+
+    fn visit_map<A>(mut self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let response = Value::deserialize(de::value::MapAccessDeserializer::new(map))?;
+        self.collect(1, response)
+    }
+}
+
+/// Parses RPC responses without collecting the input first, handing every
+/// transaction to `emit`.
+pub fn parse_responses<R, F>(reader: R, mut emit: F) -> Result<(), Error>
+where
+    R: io::Read,
+    F: FnMut(Transaction<'static>) -> Result<(), Error>,
+{
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let mut error = None;
+    let deserialized = ResponsesSeed {
+        emit: &mut emit,
+        error: &mut error,
+    }
+    .deserialize(&mut deserializer);
+    if let Err(e) = deserialized {
+        return Err(error.unwrap_or(e.into()));
+    }
+    Ok(deserializer.end()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{fixtures::CALL, log::Entry};
+
+    const RESPONSE: &str = r#"{
+        "jsonrpc": "2.0",
+        "result": {
+            "slot": 42,
+            "blockTime": 1725148800,
+            "transaction": {
+                "signatures": ["5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW"],
+                "message": {}
+            },
+            "meta": {
+                "err": null,
+                "computeUnitsConsumed": 7218,
+                "logMessages": []
+            }
+        },
+        "id": 1
+    }"#;
+
+    fn parse(input: Value) -> Vec<Transaction<'static>> {
+        let mut transactions = vec![];
+        parse_responses(input.to_string().as_bytes(), |transaction| {
+            transactions.push(transaction);
+            Ok(())
+        })
+        .unwrap();
+        transactions
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_collect_transactions() {
+        let mut response: Value = serde_json::from_str(RESPONSE).unwrap();
+        response["result"]["meta"]["logMessages"] = serde_json::json!(CALL);
+        let confirm = serde_json::json!({
+            "confirmationStatus": "finalized",
+            "transaction": response["result"].clone(),
+        });
+        let encoded = serde_json::json!({
+            "slot": 43,
+            "transaction": ["AQID", "base64"],
+            "meta": { "logMessages": [] },
+        });
+
+        let transactions = parse(Value::Array(vec![response.clone(), confirm, encoded]));
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(parse(response.clone()).len(), 1);
+
+        // Every response is handed on as soon as it is read.
+        let cut = format!("[{}, {{", response);
+        let mut emitted = 0;
+        let parsed = parse_responses(cut.as_bytes(), |_| {
+            emitted += 1;
+            Ok(())
+        });
+        assert!(matches!(parsed, Err(Error::Serde(_))));
+        assert_eq!(emitted, 1);
+
+        for transaction in &transactions[..2] {
+            assert_eq!(
+                transaction.signature.as_deref(),
+                Some("5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW")
+            );
+            assert_eq!(transaction.slot, Some(42));
+            assert_eq!(transaction.compute_units_consumed, Some(7218));
+            assert_eq!(transaction.log.inner_logs.len(), 1);
+            assert!(matches!(transaction.log.inner_logs[0], InnerLog::Invoke(_)));
+        }
+        assert_eq!(transactions[2].signature, None);
+        assert!(transactions[2].log.inner_logs.is_empty());
+
+        let text = serde_json::to_value(&transactions[0]).unwrap();
+        assert_eq!(text["slot"], 42);
+        assert_eq!(text["usage"]["consumed"], 7218);
+        assert_eq!(text["usage"]["headroom"], 200000 - 7218);
+        assert_eq!(text["logs"][0]["budget"], 200000);
+        assert_eq!(text["logs"][0]["type"], "invoke");
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_collect_missing() {
+        let responses = serde_json::json!([
+            [
+                { "slot": 41, "meta": null },
+                { "slot": 42, "meta": null },
+            ],
+            {
+                "jsonrpc": "2.0",
+                "error": { "code": -32602, "message": "Invalid param" },
+                "id": 2
+            },
+            { "jsonrpc": "2.0", "result": null, "id": 3 },
+        ]);
+        let mut transactions = parse(responses);
+        assert_eq!(transactions.len(), 4);
+
+        assert_eq!(transactions[1].slot, Some(42));
+        assert!(transactions[1].log.inner_logs.is_empty());
+
+        // Numbered by response, not by the transactions collected before.
+        let diagnostics = transactions
+            .drain(2..)
+            .map(|transaction| {
+                let entry = Entry::Transaction(transaction);
+                match &entry.diagnostics()[..] {
+                    [diagnostic] => (*diagnostic).clone(),
+                    other => panic!("{:?}", other),
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(diagnostics[0].position, Position::Response(2));
+        assert_eq!(
+            diagnostics[0].to_string(),
+            r#"response 2: expected a transaction, found `{"code":-32602,"message":"Invalid param"}`"#
+        );
+        let json = serde_json::to_value(&diagnostics[0]).unwrap();
+        assert_eq!(json["response"], 2);
+        assert_eq!(
+            serde_json::from_value::<Diagnostic>(json).unwrap(),
+            diagnostics[0]
+        );
+        assert_eq!(diagnostics[1].position, Position::Response(3));
+        assert_eq!(diagnostics[1].found, "null");
+    }
+}
@@ -0,0 +1,250 @@
+extern crate ansi_term;
+
+use std::fmt;
+use std::io::Write;
+
+use self::ansi_term::{
+    Colour::{Cyan, Red, Yellow},
+    Style,
+};
+
+use super::{
+    consumption::Consumer,
+    error::Error,
+    invoke::{BudgetUsage, Outcome},
+    log::{Entry, InnerLog, Unknown},
+};
+
+/// Renders parsed logs as an indented tree for the terminal.
+///
+/// Every node shows its `local` and `global` cost and, below an invoke, the share of
+/// that invoke's `global` it accounts for. Invokes that did not succeed and aborted
+/// functions are flagged. Consecutive lines of program output are collapsed
+/// into a count unless `verbose` is set; lines the parser could not fit into the tree
+/// are always shown.
+#[derive(Debug, Default)]
+pub struct Tree {
+    verbose: bool,
+    lines: Vec<String>,
+}
+
+impl Tree {
+    pub fn new(verbose: bool) -> Tree {
+        Tree {
+            verbose,
+            lines: vec![],
+        }
+    }
+
+    pub fn add_entry(&mut self, entry: &Entry) {
+        let depth = match entry {
+            Entry::Transaction(transaction) => {
+                let mut header = format!(
+                    "transaction {}",
+                    transaction.signature.as_deref().unwrap_or("<unknown>")
+                );
+                if let Some(slot) = transaction.slot {
+                    header.push_str(&format!(" slot {}", slot));
+                }
+                match (&transaction.usage, transaction.compute_units_consumed) {
+                    (Some(usage), _) => header.push_str(&format!(" {}", describe(usage))),
+                    (None, Some(consumed)) => header.push_str(&format!(" consumed {}", consumed)),
+                    (None, None) => {}
+                }
+                self.lines
+                    .push(Style::new().bold().paint(header).to_string());
+                1
+            }
+            Entry::InnerLog(_) => 0,
+        };
+        self.add_all(entry.inner_logs(), depth, None);
+    }
+
+    /// `total` is the `global` cost of the enclosing invoke.
+    fn add_all(&mut self, inner_logs: &[InnerLog], depth: usize, total: Option<i32>) {
+        let mut collapsed = 0;
+        for inner_log in inner_logs {
+            let shown = matches!(
+                inner_log,
+                InnerLog::Invoke(_)
+                    | InnerLog::Function(_)
+                    | InnerLog::Unknown(Unknown {
+                        diagnostic: Some(_),
+                        ..
+                    })
+            );
+            if !shown && !self.verbose {
+                collapsed += 1;
+                continue;
+            }
+            self.collapsed(depth, &mut collapsed);
+            self.add(inner_log, depth, total);
+        }
+        self.collapsed(depth, &mut collapsed);
+    }
+
+    fn collapsed(&mut self, depth: usize, collapsed: &mut usize) {
+        if *collapsed > 0 {
+            let text = format!("... {} log line(s)", collapsed);
+            self.push(depth, Style::new().dimmed().paint(text).to_string());
+            *collapsed = 0;
+        }
+    }
+
+    fn add(&mut self, inner_log: &InnerLog, depth: usize, total: Option<i32>) {
+        let (name, status, children_total) = match inner_log {
+            InnerLog::Invoke(invoke) => (
+                {
+                    let mut name = match &invoke.name {
+                        Some(name) => format!(
+                            "{} {}",
+                            Cyan.bold().paint(name),
+                            Style::new().dimmed().paint(invoke.id.as_ref())
+                        ),
+                        None => Cyan.bold().paint(invoke.id.as_ref()).to_string(),
+                    };
+                    if let Some(instruction) = &invoke.instruction {
+                        name.push_str(&format!(" {}", Cyan.paint(instruction)));
+                    }
+                    if let Some(usage) = invoke.usage() {
+                        name.push_str(&format!(" {}", describe(&usage)));
+                    }
+                    name
+                },
+                match &invoke.outcome {
+                    Some(Outcome::Failed { error }) => format!(" failed: {}", error),
+                    Some(Outcome::ExceededBudget { error }) => {
+                        format!(" exceeded budget: {}", error)
+                    }
+                    Some(Outcome::Truncated) => " truncated".to_string(),
+                    Some(Outcome::Success) | None => String::new(),
+                },
+                Some(inner_log.global_ex_log()),
+            ),
+            InnerLog::Function(function) => (
+                Yellow.paint(function.id.as_ref()).to_string(),
+                match function.aborted {
+                    true => " aborted".to_string(),
+                    false => String::new(),
+                },
+                total,
+            ),
+            InnerLog::Unknown(Unknown {
+                line,
+                diagnostic: Some(diagnostic),
+            }) => {
+                let text = format!("{} (expected {})", line, diagnostic.expected);
+                self.push(depth, Red.paint(text).to_string());
+                return;
+            }
+            InnerLog::Return(ret) => return self.leaf(depth, ret),
+            InnerLog::Data(data) => return self.leaf(depth, data),
+            InnerLog::Message(message) => return self.leaf(depth, message),
+            InnerLog::Marker(marker) => return self.leaf(depth, marker),
+            InnerLog::Unknown(unknown) => return self.leaf(depth, &unknown.line),
+        };
+        let global = inner_log.global_ex_log();
+        let share = match total {
+            Some(total) if total > 0 => format!(" {:.1}%", global as f64 / total as f64 * 100.0),
+            _ => String::new(),
+        };
+        self.push(
+            depth,
+            format!(
+                "{} local {} global {}{}{}",
+                name,
+                inner_log.local_ex_log(),
+                global,
+                share,
+                Red.paint(status)
+            ),
+        );
+        self.add_all(inner_log.inner_logs(), depth + 1, children_total);
+    }
+
+    /// Shows a line of program output, or an unknown line, dimmed.
+    fn leaf(&mut self, depth: usize, text: &dyn fmt::Display) {
+        let text = Style::new().dimmed().paint(text.to_string());
+        self.push(depth, text.to_string());
+    }
+
+    fn push(&mut self, depth: usize, line: String) {
+        self.lines.push(format!("{}{}", "  ".repeat(depth), line));
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        for line in &self.lines {
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+fn describe(usage: &BudgetUsage) -> String {
+    format!(
+        "consumed {} of {} ({:.1}%, headroom {})",
+        usage.consumed, usage.budget, usage.percent_of_budget, usage.headroom
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixtures::entries;
+
+    const INPUT: &[&str] = &[
+        "Program SOME_PROGRAM invoke [1]",
+        "Program log: Instruction: Swap",
+        "Program log: hello",
+        "Program log: fn_one {{",
+        "Program consumption: 199000 units remaining",
+        "Program consumption: 197000 units remaining",
+        "Program log: }} // fn_one",
+        "Program SOME_PROGRAM consumed 4000 of 200000 compute units",
+        "Program SOME_PROGRAM success",
+    ];
+
+    fn plain(tree: &Tree) -> Vec<String> {
+        tree.lines()
+            .iter()
+            .map(|line| {
+                let mut plain = String::new();
+                let mut escape = false;
+                for c in line.chars() {
+                    match (escape, c) {
+                        (false, '\u{1b}') => escape = true,
+                        (true, 'm') => escape = false,
+                        (false, c) => plain.push(c),
+                        _ => {}
+                    }
+                }
+                plain
+            })
+            .collect()
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_tree() {
+        let mut tree = Tree::new(false);
+        let mut verbose = Tree::new(true);
+        for entry in entries(INPUT) {
+            tree.add_entry(&entry);
+            verbose.add_entry(&entry);
+        }
+        assert_eq!(
+            plain(&tree),
+            vec![
+                "SOME_PROGRAM Swap consumed 4000 of 200000 (2.0%, headroom 196000) local 1692 global 3692",
+                "  ... 1 log line(s)",
+                "  fn_one local 2000 global 2000 54.2%",
+            ]
+        );
+        assert_eq!(verbose.lines().len(), 3);
+        assert_eq!(plain(&verbose)[1], "  hello");
+    }
+}
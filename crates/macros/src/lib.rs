@@ -1,3 +1,6 @@
+#[doc(hidden)]
+pub extern crate sol_dev_utils;
+
 /// Original [here](https://github.com/thlorenz/sol-contracts/blob/master/packages/sol-common/rust/src/lib.rs)
 /// A macro for logging compute units used by a specific code block.
 ///
@@ -43,3 +46,33 @@ macro_rules! compute_fn {
         res
     };
 }
+
+/// Logs an empty probe frame nested in another, for `sol-dev-cli parse --calibration auto`
+/// to derive what logging a frame costs on the runtime that produced the log.
+///
+/// Call it once at the start of the instruction, inside the outermost measured frame.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// sol_dev_macros::compute_fn!("process_instruction" => {
+///     sol_dev_macros::compute_probe!();
+///     // Your code here
+/// });
+/// ```
+#[macro_export]
+macro_rules! compute_probe {
+    () => {
+        ::solana_program::msg!(concat!($crate::sol_dev_utils::probe_name!(), " {{"));
+        ::solana_program::log::sol_log_compute_units();
+        ::solana_program::msg!(concat!($crate::sol_dev_utils::probe_inner_name!(), " {{"));
+        ::solana_program::log::sol_log_compute_units();
+        ::solana_program::log::sol_log_compute_units();
+        ::solana_program::msg!(concat!(
+            "}} // ",
+            $crate::sol_dev_utils::probe_inner_name!()
+        ));
+        ::solana_program::log::sol_log_compute_units();
+        ::solana_program::msg!(concat!("}} // ", $crate::sol_dev_utils::probe_name!()));
+    };
+}
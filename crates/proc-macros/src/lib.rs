@@ -1,11 +1,20 @@
 extern crate proc_macro;
+extern crate proc_macro2;
 extern crate quote;
 extern crate syn;
 
+use std::iter::Peekable;
+use std::str::Chars;
+
 use proc_macro::TokenStream;
 
-use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use quote::{quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_macro_input, Attribute, Block, Ident, ImplItem, ImplItemFn, Item, ItemImpl, ItemMod,
+    LitStr, Meta, Signature, Token, TraitItemFn, Type,
+};
 
 /// Generates a discriminant for a given function name within a global namespace.
 ///
@@ -70,14 +79,27 @@ pub fn anchor_discriminant(input: TokenStream) -> TokenStream {
 /// fn my_function() {
 ///     // Function body
 /// }
+///
+/// impl Vault {
+///     #[compute_fn]
+///     fn deposit(&mut self, amount: u64) -> Result<&Self, ProgramError> {
+///         // Method body
+///     }
+/// }
 /// ```
 ///
+/// Works on free functions, methods, trait impls and default methods of traits.
+///
 /// # Effects
 ///
 /// - Adds a log message with the function name at the start of execution.
 /// - Logs the number of compute units before and after the function execution.
 /// - Adds a closing log message with the function name at the end of execution.
 ///
+/// The closing logs are emitted by a guard that is dropped when the function returns,
+/// so the body runs as written: `return`, `?` and borrows of `self` or the arguments
+/// behave exactly as in the uninstrumented function.
+///
 /// # Note on Compute Units Used by `compute_fn!`
 ///
 /// ## Testing Results (as of 2024-09-01)
@@ -108,23 +130,833 @@ pub fn anchor_discriminant(input: TokenStream) -> TokenStream {
 ///  For more details, see:
 ///  - https://github.com/anza-xyz/agave/blob/d88050cda335f87e872eddbdf8506bc063f039d3/programs/bpf_loader/src/syscalls/logging.rs#L70
 ///  - https://github.com/anza-xyz/agave/blob/d88050cda335f87e872eddbdf8506bc063f039d3/program-runtime/src/compute_budget.rs#L150
+///
+/// # Probe
+///
+/// The costs above differ between runtimes. With `#[compute_fn(probe)]`, typically on
+/// the entrypoint, the function logs an empty probe frame nested in another before
+/// its body, from which `sol-dev-cli parse --calibration auto` derives the costs of
+/// the runtime that produced the log.
+///
+/// # Qualified names
+///
+/// Functions are logged by their name only, so functions with the same name in
+/// different modules end up as one. With `#[compute_fn(qualified)]` the name is
+/// prefixed with `module_path!()`, e.g. `my_program::vault::deposit`. The type of a
+/// method is not known to `compute_fn`, so `qualified` is an error on functions that
+/// take `self` or mention `Self`, and on default methods of traits; use
+/// `#[compute_impl(qualified)]` on the `impl` block for those.
+///
+/// # Arguments
+///
+/// - `name = "swap_leg"` logs the function as `swap_leg` instead of by its name.
+/// - `feature = "compute-fn"` only instruments the function when the feature is enabled,
+///   in place of `#[cfg_attr(feature = "compute-fn", compute_fn)]`.
+/// - `skip_if = cfg(test)` leaves the function as is when the predicate holds.
+/// - `log = "text"` picks how frames are logged. `text`, the default, logs the name
+///   with `msg!` as shown above. `binary`, see below.
+/// - `probe` and `qualified`, see above.
+///
+/// Arguments can be combined, e.g.
+/// `#[compute_fn(name = "swap_leg", feature = "compute-fn", skip_if = cfg(test))]`.
+///
+/// There is no argument to sample calls: deciding which calls to log needs a counter,
+/// and programs have no writable static data and keep nothing between instructions.
+/// Every call is logged; to log fewer, instrument fewer functions, or gate them with
+/// `feature` or `skip_if`.
+///
+/// # Binary logs
+///
+/// With `log = "binary"` the function only reads the units remaining when it starts,
+/// and logs a single `sol_log_64` of a tag, the id of the name and the units remaining
+/// at its start and end when it returns, e.g.
+/// `Program log: 0x736f6c5f646576, 0x9a4d0c5e2f3b1a77, 0x30d1a, 0x30a2b, 0x0`.
+/// That is three syscalls per frame rather than four, and the cost does not depend on
+/// the name. `sol-dev-cli` nests the frames by their units remaining. Functions that
+/// never return, e.g. on a panic, are not logged.
+///
+/// The id is `sol_dev_utils::frame_id` of the name, so the names stay out of the
+/// program. When `$SOL_DEV_FRAMES` is set to a directory, each name is written there
+/// to `<crate>/<id>` for `sol-dev-cli parse --frames`. Qualified names are not
+/// supported, as `module_path!()` is only known after the macro expands.
+///
+/// The names are written while the macro expands, so by every expansion that sees the
+/// variable, `cargo check` and editors included, and only when the crate is rebuilt.
+/// Names of functions that were renamed or removed stay behind; they match no id of
+/// the current build, but to keep only its names clear the directory and rebuild.
 #[proc_macro_attribute]
-pub fn compute_fn(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    let mut input = parse_macro_input!(item as ItemFn);
-    let fn_name = &input.sig.ident;
-    let block = &input.block;
-
-    input.block = syn::parse_quote!({
-        ::solana_program::msg!(concat!(stringify!(#fn_name), " {{"));
-        ::solana_program::log::sol_log_compute_units();
+pub fn compute_fn(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let options = match Options::parse(
+        attr.into(),
+        &["name", "feature", "skip_if", "log", "probe", "qualified"],
+    ) {
+        Ok(options) => options,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let item = proc_macro2::TokenStream::from(item);
+    // Free functions and methods in impl blocks, then default methods in traits.
+    let expanded = match syn::parse2::<ImplItemFn>(item.clone()) {
+        Ok(mut input) => check(&input.sig, &options, false).map(|()| {
+            let name = options.name(&input.sig.ident);
+            input.block = instrument(&name, &input.block, &options);
+            quote!(#input)
+        }),
+        Err(e) => match syn::parse2::<TraitItemFn>(item) {
+            Ok(mut input) => {
+                check(&input.sig, &options, true).and_then(|()| match &input.default {
+                    Some(block) => {
+                        let name = options.name(&input.sig.ident);
+                        input.default = Some(instrument(&name, block, &options));
+                        Ok(quote!(#input))
+                    }
+                    None => Err(syn::Error::new_spanned(
+                        &input.sig,
+                        "compute_fn needs a function body",
+                    )),
+                })
+            }
+            Err(_) => Err(e),
+        },
+    };
+    expanded.unwrap_or_else(|e| e.to_compile_error()).into()
+}
 
-        let __result = (|| #block)();
+/// Fails on functions `compute_fn` cannot instrument with `options`, `in_trait` for
+/// default methods of traits.
+fn check(sig: &Signature, options: &Options, in_trait: bool) -> syn::Result<()> {
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "compute_fn does not support async functions",
+        ));
+    }
+    let method = sig.receiver().is_some() || mentions_self(sig.to_token_stream());
+    if options.qualified && (in_trait || method) {
+        return Err(syn::Error::new_spanned(
+            &sig.ident,
+            "compute_fn(qualified) cannot name the type of a method, use \
+             #[compute_impl(qualified)] on its impl block instead",
+        ));
+    }
+    Ok(())
+}
 
-        ::solana_program::log::sol_log_compute_units();
-        ::solana_program::msg!(concat!("}} // ", stringify!(#fn_name)));
+/// Whether `tokens` mention `Self`.
+fn mentions_self(tokens: proc_macro2::TokenStream) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) => ident == "Self",
+        proc_macro2::TokenTree::Group(group) => mentions_self(group.stream()),
+        _ => false,
+    })
+}
 
-        __result
-    });
+/// Attribute macro for instrumenting every function in an `impl` block, as if each
+/// had `#[compute_fn]`.
+///
+/// Functions are logged with the name of the type, e.g. `Vault::deposit`. Functions
+/// with `#[compute_skip]` or their own `#[compute_fn]`, also under `cfg_attr`, are left
+/// out, as are `async` and `const` functions.
+///
+/// With `#[compute_impl(qualified)]`, names are prefixed with `module_path!()` and
+/// the type is written out in full, e.g. `my_program::Vault<T>::deposit` or
+/// `my_program::<Vault as Named>::name` in a trait impl. Types that cannot be written
+/// in a name, such as tuples, are named as without `qualified`, which leaves them out,
+/// e.g. `my_program::name`. `feature`, `skip_if` and `log` apply to every function,
+/// see `compute_fn`.
+///
+/// # Usage
+///
+/// ```rust,ignore
+/// #[compute_impl]
+/// impl Vault {
+///     fn deposit(&mut self, amount: u64) {
+///         // Logged as Vault::deposit
+///     }
+///
+///     #[compute_skip]
+///     fn amount(&self) -> u64 {
+///         self.amount
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn compute_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let options = match Options::parse(attr.into(), &["feature", "skip_if", "log", "qualified"]) {
+        Ok(options) => options,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let mut input = parse_macro_input!(item as ItemImpl);
+    instrument_impl(&mut input, "", &options);
+    quote!(#input).into()
+}
 
+/// Attribute macro for instrumenting every function in an inline module, as if each
+/// had `#[compute_fn]`, including the functions of its `impl` blocks and nested modules.
+///
+/// Functions are logged with their path in the module, e.g. `vault::deposit` or
+/// `vault::Vault::deposit`. Items with `#[compute_skip]` are left out, see `compute_impl`.
+/// With `#[compute_mod(qualified)]`, the path starts at the crate instead, as in
+/// `module_path!()`. Takes the same arguments as `compute_impl`.
+///
+/// # Usage
+///
+/// ```rust,ignore
+/// #[compute_mod]
+/// mod vault {
+///     pub fn deposit(amount: u64) {
+///         // Logged as vault::deposit
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn compute_mod(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let options = match Options::parse(attr.into(), &["feature", "skip_if", "log", "qualified"]) {
+        Ok(options) => options,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let mut input = parse_macro_input!(item as ItemMod);
+    if input.content.is_none() {
+        return syn::Error::new_spanned(&input, "compute_mod needs an inline module")
+            .to_compile_error()
+            .into();
+    }
+    instrument_mod(&mut input, "", &options);
     quote!(#input).into()
 }
+
+/// Leaves an item out of `compute_impl` and `compute_mod`.
+#[proc_macro_attribute]
+pub fn compute_skip(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    item
+}
+
+/// How frames are logged.
+#[derive(Default)]
+enum Flavor {
+    /// `msg!` with the name, see `compute_fn`.
+    #[default]
+    Text,
+    /// `sol_log_64` with the id of the name, see `compute_fn`.
+    Binary,
+}
+
+impl Flavor {
+    /// Logs the start of the frame `name`, or for binary logs keeps the units remaining
+    /// in `start`.
+    fn enter(&self, name: &Name, start: &Ident) -> proc_macro2::TokenStream {
+        match self {
+            Flavor::Text => quote! {
+                ::solana_program::msg!(concat!(#name, " {{"));
+                ::solana_program::log::sol_log_compute_units();
+            },
+            Flavor::Binary => quote! {
+                let #start = ::solana_program::compute_units::sol_remaining_compute_units();
+            },
+        }
+    }
+
+    /// Logs the units remaining and the end of the frame `name`, for binary logs in a
+    /// single `sol_log_64` together with the id and the units remaining at `start`.
+    fn exit(&self, name: &Name, start: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            Flavor::Text => quote! {
+                ::solana_program::log::sol_log_compute_units();
+                ::solana_program::msg!(concat!("}} // ", #name));
+            },
+            Flavor::Binary => {
+                let tag = sol_dev_utils::FRAME_TAG;
+                let id = sol_dev_utils::frame_id(&name.path.value());
+                let recorded = match record(id, &name.path.value()) {
+                    Ok(()) => quote!(),
+                    Err(e) => {
+                        let message =
+                            format!("could not write the frame map to ${}: {}", FRAMES, e);
+                        quote!(compile_error!(#message);)
+                    }
+                };
+                quote! {
+                    #recorded
+                    ::solana_program::log::sol_log_64(
+                        #tag,
+                        #id,
+                        #start,
+                        ::solana_program::compute_units::sol_remaining_compute_units(),
+                        0,
+                    );
+                }
+            }
+        }
+    }
+
+    /// The field of the guard that keeps what `exit` needs, and its value.
+    fn guard(&self, start: &Ident) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+        match self {
+            Flavor::Text => (quote!(), quote!()),
+            Flavor::Binary => (quote!((u64)), quote!((#start))),
+        }
+    }
+}
+
+/// The directory `log = "binary"` writes the names of frames to, by id.
+const FRAMES: &str = "SOL_DEV_FRAMES";
+
+/// Writes `name` to `$SOL_DEV_FRAMES/<crate>/<id>`, if `$SOL_DEV_FRAMES` is set.
+/// Nothing is ever removed, see `compute_fn`.
+///
+/// Crates and functions expand in parallel, so every id gets a file of its own, which
+/// is renamed into place once written. Ids are hashes of names, so any writer of the
+/// same file writes the same name.
+fn record(id: u64, name: &str) -> std::io::Result<()> {
+    let dir = match std::env::var_os(FRAMES) {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => return Ok(()),
+    };
+    let krate = std::env::var("CARGO_CRATE_NAME").unwrap_or_else(|_| "frames".to_string());
+    let dir = dir.join(krate);
+    let file = format!("{:#018x}", id);
+    let path = dir.join(&file);
+    if std::fs::read_to_string(&path).is_ok_and(|recorded| recorded == name) {
+        return Ok(());
+    }
+    std::fs::create_dir_all(&dir)?;
+    let temporary = dir.join(format!(".{}.{}", file, std::process::id()));
+    std::fs::write(&temporary, name)?;
+    std::fs::rename(temporary, path)
+}
+
+/// Arguments of `compute_fn`, `compute_impl` and `compute_mod`.
+#[derive(Default)]
+struct Options {
+    /// Logged in place of the function name.
+    name: Option<LitStr>,
+    /// Only instrument with this feature enabled.
+    feature: Option<LitStr>,
+    /// A cfg predicate under which not to instrument.
+    skip_if: Option<proc_macro2::TokenStream>,
+    flavor: Flavor,
+    /// Log a probe frame before the body.
+    probe: bool,
+    /// Prefix names with `module_path!()` and write out the type of `impl` blocks.
+    qualified: bool,
+}
+
+impl Options {
+    /// Parses `attr`, a comma separated list of the arguments in `allowed`.
+    fn parse(attr: proc_macro2::TokenStream, allowed: &[&str]) -> syn::Result<Options> {
+        let mut options = Options::default();
+        let parser = syn::meta::parser(|meta| {
+            let key = match meta.path.get_ident() {
+                Some(ident) if allowed.iter().any(|key| ident == key) => ident.to_string(),
+                _ => return Err(meta.error(format!("expected one of: {}", allowed.join(", ")))),
+            };
+            match key.as_str() {
+                "name" => {
+                    let name: LitStr = meta.value()?.parse()?;
+                    if !is_parsed_name(&name.value()) {
+                        return Err(syn::Error::new_spanned(
+                            name,
+                            "expected a name like `swap_leg` or `Vault::deposit`",
+                        ));
+                    }
+                    options.name = Some(name);
+                }
+                "feature" => options.feature = Some(meta.value()?.parse()?),
+                "skip_if" => {
+                    let value = meta.value()?;
+                    let cfg: Ident = value.parse()?;
+                    if cfg != "cfg" {
+                        return Err(syn::Error::new_spanned(cfg, "expected `cfg(...)`"));
+                    }
+                    let predicate;
+                    syn::parenthesized!(predicate in value);
+                    options.skip_if = Some(predicate.parse()?);
+                }
+                "log" => {
+                    let flavor: LitStr = meta.value()?.parse()?;
+                    options.flavor = match flavor.value().as_str() {
+                        "text" => Flavor::Text,
+                        "binary" => Flavor::Binary,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                flavor,
+                                "expected \"text\" or \"binary\"",
+                            ))
+                        }
+                    };
+                }
+                "probe" => options.probe = true,
+                _ => options.qualified = true,
+            }
+            Ok(())
+        });
+        syn::parse::Parser::parse2(parser, attr)?;
+        if options.qualified && matches!(options.flavor, Flavor::Binary) {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "log = \"binary\" cannot log qualified names, the module path is not known to the macro",
+            ));
+        }
+        Ok(options)
+    }
+
+    /// The name of a function of its own, see `compute_fn`.
+    fn name(&self, function: &Ident) -> Name {
+        match &self.name {
+            Some(name) => Name {
+                qualified: self.qualified,
+                path: match self.qualified {
+                    true => LitStr::new(&format!("::{}", name.value()), name.span()),
+                    false => name.clone(),
+                },
+            },
+            None => Name::new(self.qualified, "", function),
+        }
+    }
+
+    /// The `cfg` attribute the instrumentation is compiled under, if any.
+    fn gate(&self) -> proc_macro2::TokenStream {
+        let mut predicates = vec![];
+        if let Some(feature) = &self.feature {
+            predicates.push(quote!(feature = #feature));
+        }
+        if let Some(skip_if) = &self.skip_if {
+            predicates.push(quote!(not(#skip_if)));
+        }
+        match predicates.is_empty() {
+            true => quote!(),
+            false => quote!(#[cfg(all(#(#predicates),*))]),
+        }
+    }
+}
+
+/// How deep `sol-dev-cli` reads angle brackets in a name.
+const MAX_NESTING: usize = 3;
+
+/// Whether `sol-dev-cli` reads `name` as a function name: `::` separated segments,
+/// each a word with optional generic arguments or a qualified type on its own, e.g.
+/// `Vault<T>::deposit` or `<Vault<T> as Named>::name`. Spaces, `&`, `,` and `'` are
+/// only read inside angle brackets, nested up to `MAX_NESTING` deep.
+fn is_parsed_name(name: &str) -> bool {
+    let mut chars = name.chars().peekable();
+    loop {
+        if !parse_segment(&mut chars) {
+            return false;
+        }
+        match chars.next() {
+            None => return true,
+            Some(':') if chars.next() == Some(':') => {}
+            _ => return false,
+        }
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Reads a word with optional generic arguments, or generic arguments on their own.
+fn parse_segment(chars: &mut Peekable<Chars>) -> bool {
+    let mut word = false;
+    while chars.next_if(|&c| is_word(c)).is_some() {
+        word = true;
+    }
+    match chars.peek() {
+        Some('<') => parse_generic(chars, 1),
+        _ => word,
+    }
+}
+
+/// Reads `<...>`, nested `depth` deep.
+fn parse_generic(chars: &mut Peekable<Chars>, depth: usize) -> bool {
+    if depth > MAX_NESTING || chars.next() != Some('<') {
+        return false;
+    }
+    loop {
+        match chars.peek() {
+            Some('>') => {
+                chars.next();
+                return true;
+            }
+            Some('<') => {
+                if !parse_generic(chars, depth + 1) {
+                    return false;
+                }
+            }
+            Some(&c) if is_word(c) || ":&,' ".contains(c) => {
+                chars.next();
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Whether `attr` is `name`, however it is imported.
+fn is_attribute(attr: &Attribute, name: &str) -> bool {
+    attr.path().segments.last().is_some_and(|s| s.ident == name)
+}
+
+/// Whether the item with `attrs` is left to instrument itself or skipped,
+/// removing `#[compute_skip]`.
+fn is_excluded(attrs: &mut Vec<Attribute>) -> bool {
+    let skipped = attrs.iter().any(|attr| is_attribute(attr, "compute_skip"));
+    attrs.retain(|attr| !is_attribute(attr, "compute_skip"));
+    skipped || attrs.iter().any(is_instrumenting)
+}
+
+/// Whether `attr` instruments the item itself, directly or through `cfg_attr`,
+/// e.g. `#[cfg_attr(feature = "compute-fn", compute_fn)]`.
+fn is_instrumenting(attr: &Attribute) -> bool {
+    const INSTRUMENTING: &[&str] = &["compute_fn", "compute_impl", "compute_mod"];
+    if INSTRUMENTING.iter().any(|name| is_attribute(attr, name)) {
+        return true;
+    }
+    if !attr.path().is_ident("cfg_attr") {
+        return false;
+    }
+    attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+        .is_ok_and(|metas| {
+            metas.iter().skip(1).any(|meta| {
+                meta.path()
+                    .segments
+                    .last()
+                    .is_some_and(|s| INSTRUMENTING.iter().any(|name| s.ident == name))
+            })
+        })
+}
+
+/// Functions that cannot log.
+fn is_excluded_sig(sig: &Signature) -> bool {
+    sig.asyncness.is_some() || sig.constness.is_some()
+}
+
+/// The name a function is logged with, `prefix::function`, preceded by
+/// `module_path!()` if qualified.
+struct Name {
+    qualified: bool,
+    path: LitStr,
+}
+
+impl Name {
+    fn plain(name: &str) -> Name {
+        Name {
+            qualified: false,
+            path: LitStr::new(name, proc_macro2::Span::call_site()),
+        }
+    }
+
+    fn new(qualified: bool, prefix: &str, function: &Ident) -> Name {
+        let path = match (qualified, prefix) {
+            (false, "") => function.unraw().to_string(),
+            (false, prefix) => format!("{}::{}", prefix, function.unraw()),
+            (true, "") => format!("::{}", function.unraw()),
+            (true, prefix) => format!("::{}::{}", prefix, function.unraw()),
+        };
+        Name {
+            qualified,
+            path: LitStr::new(&path, function.span()),
+        }
+    }
+}
+
+/// The arguments of `concat!` that make up the name.
+impl ToTokens for Name {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let path = &self.path;
+        tokens.extend(match self.qualified {
+            true => quote!(module_path!(), #path),
+            false => quote!(#path),
+        });
+    }
+}
+
+/// The name of the type of an `impl` block, without generics.
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|s| s.ident.unraw().to_string()),
+        Type::Reference(reference) => type_name(&reference.elem),
+        Type::Paren(paren) => type_name(&paren.elem),
+        Type::Group(group) => type_name(&group.elem),
+        _ => None,
+    }
+}
+
+/// `tokens` as they would be written, e.g. `Vault<T>` rather than `Vault < T >`.
+fn written(tokens: &dyn ToTokens) -> String {
+    let mut written = tokens.to_token_stream().to_string();
+    for (from, to) in [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" < ", "<"),
+        ("< ", "<"),
+        (" <", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        ("& ", "&"),
+    ] {
+        written = written.replace(from, to);
+    }
+    written
+}
+
+/// The name of the `Self` type of an `impl` block, in full if qualified.
+fn self_name(input: &ItemImpl, qualified: bool) -> Option<String> {
+    if !qualified {
+        return type_name(&input.self_ty);
+    }
+    let self_ty = written(&input.self_ty);
+    let name = match &input.trait_ {
+        Some((_, path, _)) => format!("<{} as {}>", self_ty, written(path)),
+        None => self_ty,
+    };
+    // Tuples, arrays, slices and function pointers are written with characters that
+    // `sol-dev-cli` does not read as part of a name. The name follows `module_path!()`.
+    match is_parsed_name(&name) {
+        true => Some(name),
+        false => type_name(&input.self_ty),
+    }
+}
+
+/// Joins the non-empty parts of a logged name.
+fn join(prefix: &str, name: Option<String>) -> String {
+    match name {
+        Some(name) if !prefix.is_empty() => format!("{}::{}", prefix, name),
+        Some(name) => name,
+        None => prefix.to_string(),
+    }
+}
+
+fn instrument_impl(input: &mut ItemImpl, prefix: &str, options: &Options) {
+    let prefix = join(prefix, self_name(input, options.qualified));
+    for item in &mut input.items {
+        if let ImplItem::Fn(function) = item {
+            if is_excluded(&mut function.attrs) || is_excluded_sig(&function.sig) {
+                continue;
+            }
+            let name = Name::new(options.qualified, &prefix, &function.sig.ident);
+            function.block = instrument(&name, &function.block, options);
+        }
+    }
+}
+
+fn instrument_mod(input: &mut ItemMod, prefix: &str, options: &Options) {
+    // `module_path!()` already includes the module when qualified.
+    let prefix = match options.qualified {
+        true => String::new(),
+        false => join(prefix, Some(input.ident.unraw().to_string())),
+    };
+    let items = match &mut input.content {
+        Some((_, items)) => items,
+        None => return,
+    };
+    for item in items {
+        let attrs = match item {
+            Item::Fn(function) => &mut function.attrs,
+            Item::Impl(item_impl) => &mut item_impl.attrs,
+            Item::Mod(item_mod) => &mut item_mod.attrs,
+            _ => continue,
+        };
+        if is_excluded(attrs) {
+            continue;
+        }
+        match item {
+            Item::Fn(function) if !is_excluded_sig(&function.sig) => {
+                let name = Name::new(options.qualified, &prefix, &function.sig.ident);
+                *function.block = instrument(&name, &function.block, options);
+            }
+            Item::Impl(item_impl) => instrument_impl(item_impl, &prefix, options),
+            Item::Mod(item_mod) => instrument_mod(item_mod, &prefix, options),
+            _ => {}
+        }
+    }
+}
+
+/// Logs the start of `block` and, through a guard that is dropped however the
+/// function returns, its end. The statements of the block are left as they are.
+fn instrument(name: &Name, block: &Block, options: &Options) -> Block {
+    let flavor = &options.flavor;
+    let ident = |name: &str| Ident::new(name, proc_macro2::Span::call_site());
+    let probe = match options.probe {
+        true => {
+            let (outer, outer_start) = (Name::plain(sol_dev_utils::PROBE), ident("__probe_start"));
+            let (inner, inner_start) = (
+                Name::plain(sol_dev_utils::PROBE_INNER),
+                ident("__probe_inner_start"),
+            );
+            let (enter_outer, enter_inner) = (
+                flavor.enter(&outer, &outer_start),
+                flavor.enter(&inner, &inner_start),
+            );
+            let (exit_inner, exit_outer) = (
+                flavor.exit(&inner, quote!(#inner_start)),
+                flavor.exit(&outer, quote!(#outer_start)),
+            );
+            quote!(#enter_outer #enter_inner #exit_inner #exit_outer)
+        }
+        false => quote!(),
+    };
+    let start = ident("__compute_fn_start");
+    let (enter, exit) = (
+        flavor.enter(name, &start),
+        flavor.exit(name, quote!(self.0)),
+    );
+    let (field, value) = flavor.guard(&start);
+    let gate = options.gate();
+    let stmts = &block.stmts;
+    // The guard is declared inside the initializer, so that stacked instrumentation
+    // declares guards of its own rather than a second one in the same scope.
+    syn::parse_quote!({
+        #gate
+        let __compute_fn_guard = {
+            struct __ComputeFnGuard #field;
+            impl Drop for __ComputeFnGuard {
+                fn drop(&mut self) {
+                    #exit
+                }
+            }
+            #enter
+            #probe
+            __ComputeFnGuard #value
+        };
+
+        #(#stmts)*
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALLOWED: &[&str] = &["name", "feature", "skip_if", "log", "probe", "qualified"];
+
+    fn error(attr: proc_macro2::TokenStream, allowed: &[&str]) -> String {
+        match Options::parse(attr, allowed) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let options = Options::parse(
+            quote!(
+                name = "swap_leg",
+                feature = "compute-fn",
+                skip_if = cfg(test),
+                log = "binary",
+                probe
+            ),
+            ALLOWED,
+        )
+        .unwrap();
+        assert_eq!(options.name.unwrap().value(), "swap_leg");
+        assert_eq!(options.feature.unwrap().value(), "compute-fn");
+        assert_eq!(options.skip_if.unwrap().to_string(), "test");
+        assert!(matches!(options.flavor, Flavor::Binary));
+        assert!(options.probe);
+        assert!(!options.qualified);
+
+        let options = Options::parse(quote!(), ALLOWED).unwrap();
+        assert!(matches!(options.flavor, Flavor::Text));
+        assert!(options.gate().is_empty());
+    }
+
+    #[test]
+    fn test_self_name() {
+        let name = |input: ItemImpl| self_name(&input, true);
+        assert_eq!(
+            name(syn::parse_quote!(
+                impl<'a, T> Named for Vault<'a, T> {}
+            )),
+            Some("<Vault<'a, T> as Named>".to_string())
+        );
+        assert_eq!(
+            name(syn::parse_quote!(
+                impl<'a> Named for &'a mut crate::Vault {}
+            )),
+            Some("<&'a mut crate::Vault as Named>".to_string())
+        );
+        assert_eq!(name(syn::parse_quote!(impl Named for (u8, Vault) {})), None);
+        assert_eq!(name(syn::parse_quote!(impl Named for [Vault; 2] {})), None);
+        assert_eq!(
+            name(syn::parse_quote!(impl Named for fn(u8) -> u8 {})),
+            None
+        );
+        assert_eq!(name(syn::parse_quote!(impl Named for &[Vault] {})), None);
+        assert_eq!(
+            name(syn::parse_quote!(impl Named for Box<[Vault]> {})),
+            Some("Box".to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_parsed_name() {
+        for name in [
+            "swap_leg",
+            "Vault::deposit",
+            "my_program::Vault<T>::deposit",
+            "my_program::<Vault<'a, Box<T>> as Named>::name",
+            "<&'a mut crate::Vault as Named>::name",
+        ] {
+            assert!(is_parsed_name(name), "{}", name);
+        }
+        for name in [
+            "",
+            "swap leg",
+            "Instruction: Swap",
+            "Vault:deposit",
+            "Vault::",
+            "Vault<T>deposit",
+            "Vault<T",
+            "&Vault::deposit",
+            "<A<B<C<D>>>>::name",
+        ] {
+            assert!(!is_parsed_name(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            error(quote!(name = "x"), &["feature", "log"]),
+            "expected one of: feature, log"
+        );
+        assert_eq!(
+            error(quote!(name = "swap leg!"), ALLOWED),
+            "expected a name like `swap_leg` or `Vault::deposit`"
+        );
+        assert_eq!(
+            error(quote!(skip_if = test), ALLOWED),
+            "expected `cfg(...)`"
+        );
+        assert_eq!(
+            error(quote!(log = "json"), ALLOWED),
+            "expected \"text\" or \"binary\""
+        );
+        assert!(error(quote!(log = "binary", qualified), ALLOWED).contains("qualified"));
+        assert!(Options::parse(quote!(name = swap_leg), ALLOWED).is_err());
+    }
+
+    #[test]
+    fn test_check_qualified() {
+        let qualified = Options::parse(quote!(qualified), ALLOWED).unwrap();
+        let check = |sig: Signature, in_trait: bool| check(&sig, &qualified, in_trait);
+        assert!(check(syn::parse_quote!(fn deposit(amount: u64)), false).is_ok());
+        assert!(check(syn::parse_quote!(fn deposit(vault: self::Vault)), false).is_ok());
+        assert!(check(syn::parse_quote!(fn deposit(amount: u64)), true).is_err());
+        for sig in [
+            syn::parse_quote!(fn deposit(&mut self, amount: u64)),
+            syn::parse_quote!(fn deposit(self: Box<Self>)),
+            syn::parse_quote!(fn new() -> Self),
+            syn::parse_quote!(fn get(vaults: &[Self]) -> Option<&Self>),
+        ] {
+            let error = check(sig, false).unwrap_err().to_string();
+            assert!(error.contains("compute_impl(qualified)"));
+        }
+        let plain = Options::parse(quote!(), ALLOWED).unwrap();
+        assert!(super::check(&syn::parse_quote!(fn new() -> Self), &plain, true).is_ok());
+    }
+}
@@ -0,0 +1,212 @@
+// `feature = "compute-fn"` is never enabled here.
+#![allow(unexpected_cfgs)]
+
+extern crate sol_dev_proc_macros;
+extern crate sol_dev_utils;
+extern crate solana_program;
+
+// `compute_skip` is removed by `compute_impl` and `compute_mod` before it is resolved.
+use sol_dev_proc_macros::{compute_fn, compute_impl, compute_mod};
+use sol_dev_utils::{frame_id, FRAME_TAG};
+use solana_program::SYSCALL;
+
+/// The lines logged so far, with the units remaining left out.
+fn logs() -> Vec<String> {
+    solana_program::take_logs()
+        .into_iter()
+        .map(|line| match line.starts_with("Program consumption: ") {
+            true => "Program consumption: _".to_string(),
+            false => line,
+        })
+        .collect()
+}
+
+/// The lines of a frame with `inner` logged inside.
+fn frame(name: &str, inner: Vec<String>) -> Vec<String> {
+    let mut lines = vec![
+        format!("Program log: {} {{{{", name),
+        "Program consumption: _".to_string(),
+    ];
+    lines.extend(inner);
+    lines.push("Program consumption: _".to_string());
+    lines.push(format!("Program log: }}}} // {}", name));
+    lines
+}
+
+struct Vault {
+    amount: u64,
+}
+
+impl Vault {
+    #[compute_fn]
+    fn withdraw(&mut self, amount: u64) -> Result<&u64, &'static str> {
+        if amount == 0 {
+            return Err("nothing to withdraw");
+        }
+        self.amount = self
+            .amount
+            .checked_sub(amount)
+            .ok_or("insufficient funds")?;
+        Ok(&self.amount)
+    }
+}
+
+#[test]
+fn test_returns() {
+    let mut vault = Vault { amount: 10 };
+    assert_eq!(vault.withdraw(4), Ok(&6));
+    assert_eq!(logs(), frame("withdraw", vec![]));
+    assert_eq!(vault.withdraw(0), Err("nothing to withdraw"));
+    assert_eq!(logs(), frame("withdraw", vec![]));
+    assert_eq!(vault.withdraw(7), Err("insufficient funds"));
+    assert_eq!(logs(), frame("withdraw", vec![]));
+    assert_eq!(vault.amount, 6);
+}
+
+trait Named {
+    #[compute_fn]
+    fn name(&self) -> &'static str {
+        solana_program::msg!("named");
+        "vault"
+    }
+}
+
+impl Named for Vault {}
+
+#[test]
+fn test_trait_default() {
+    assert_eq!(Vault { amount: 0 }.name(), "vault");
+    assert_eq!(
+        logs(),
+        frame("name", vec!["Program log: named".to_string()])
+    );
+}
+
+struct Pool<T>(T);
+
+#[compute_impl]
+impl<T> Pool<T> {
+    fn swap(&self) -> u8 {
+        self.amount()
+    }
+
+    #[compute_skip]
+    fn amount(&self) -> u8 {
+        1
+    }
+
+    #[compute_fn(name = "pool_fee")]
+    fn fee(&self) {}
+}
+
+#[compute_impl(qualified)]
+impl<T> Named for Pool<T> {
+    fn name(&self) -> &'static str {
+        "pool"
+    }
+}
+
+#[compute_impl(qualified)]
+impl Named for (u8, u8) {
+    fn name(&self) -> &'static str {
+        "pair"
+    }
+}
+
+#[compute_mod]
+mod market {
+    pub fn open() {
+        book::Book.insert();
+    }
+
+    pub mod book {
+        pub struct Book;
+
+        impl Book {
+            pub fn insert(&self) {}
+        }
+    }
+}
+
+#[test]
+fn test_impl_and_mod_names() {
+    let pool = Pool(());
+    assert_eq!(pool.swap(), 1);
+    assert_eq!(logs(), frame("Pool::swap", vec![]));
+    pool.fee();
+    assert_eq!(logs(), frame("pool_fee", vec![]));
+    assert_eq!(pool.name(), "pool");
+    assert_eq!(
+        logs(),
+        frame("compute_fn::<Pool<T> as Named>::name", vec![])
+    );
+    assert_eq!((0, 0).name(), "pair");
+    assert_eq!(logs(), frame("compute_fn::name", vec![]));
+    market::open();
+    assert_eq!(
+        logs(),
+        frame("market::open", frame("market::book::Book::insert", vec![]))
+    );
+}
+
+#[compute_fn(feature = "compute-fn")]
+fn without_feature() {}
+
+#[compute_fn(skip_if = cfg(test))]
+fn skipped_in_test() {}
+
+#[compute_fn(skip_if = cfg(not(test)))]
+fn kept_in_test() {}
+
+#[test]
+fn test_gates() {
+    without_feature();
+    skipped_in_test();
+    assert_eq!(logs(), Vec::<String>::new());
+    kept_in_test();
+    assert_eq!(logs(), frame("kept_in_test", vec![]));
+}
+
+#[compute_fn(probe)]
+fn probed() {}
+
+#[compute_fn(log = "binary", probe)]
+fn binary() {
+    solana_program::msg!("binary");
+}
+
+/// The units remaining at the start and end of a binary frame, if `line` is one for `name`.
+fn binary_frame(line: &str, name: &str) -> Option<(u64, u64)> {
+    let fields = line
+        .strip_prefix("Program log: ")?
+        .split(", ")
+        .map(|field| u64::from_str_radix(field.strip_prefix("0x")?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    match fields[..] {
+        [tag, id, start, end, 0] if tag == FRAME_TAG && id == frame_id(name) => Some((start, end)),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_probe() {
+    probed();
+    let probe = frame("sol_dev_probe", frame("sol_dev_probe_inner", vec![]));
+    assert_eq!(logs(), frame("probed", probe));
+}
+
+#[test]
+fn test_binary() {
+    binary();
+    let lines = solana_program::take_logs();
+    assert_eq!(lines.len(), 4);
+    let (inner_start, inner_end) = binary_frame(&lines[0], "sol_dev_probe_inner").unwrap();
+    let (outer_start, outer_end) = binary_frame(&lines[1], "sol_dev_probe").unwrap();
+    assert_eq!(lines[2], "Program log: binary");
+    let (start, end) = binary_frame(&lines[3], "binary").unwrap();
+    // Every frame reads the units remaining on either side and logs once, at the end.
+    assert_eq!(inner_start - inner_end, SYSCALL);
+    assert_eq!(outer_start - outer_end, 4 * SYSCALL);
+    assert_eq!(start - end, (6 + 1 + 1) * SYSCALL);
+    assert!(start > outer_start && outer_start > inner_start);
+}
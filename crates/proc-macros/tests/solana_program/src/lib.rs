@@ -0,0 +1,63 @@
+//! The parts of `solana_program` that `compute_fn` expands to. Lines are logged to a
+//! buffer per thread rather than to the runtime, and every call costs `SYSCALL` units.
+
+use std::cell::{Cell, RefCell};
+
+/// The units every syscall consumes.
+pub const SYSCALL: u64 = 100;
+
+thread_local! {
+    static REMAINING: Cell<u64> = const { Cell::new(200_000) };
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+/// Consumes a syscall and returns the units remaining.
+fn consume() -> u64 {
+    REMAINING.with(|remaining| {
+        remaining.set(remaining.get() - SYSCALL);
+        remaining.get()
+    })
+}
+
+#[doc(hidden)]
+pub fn sol_log(message: &str) {
+    consume();
+    LOGS.with(|logs| logs.borrow_mut().push(format!("Program log: {}", message)));
+}
+
+/// Takes the lines logged on this thread so far.
+pub fn take_logs() -> Vec<String> {
+    LOGS.with(|logs| logs.borrow_mut().drain(..).collect())
+}
+
+#[macro_export]
+macro_rules! msg {
+    ($msg:expr) => {
+        $crate::sol_log($msg)
+    };
+}
+
+pub mod log {
+    pub fn sol_log_compute_units() {
+        let remaining = super::consume();
+        super::LOGS.with(|logs| {
+            logs.borrow_mut().push(format!(
+                "Program consumption: {} units remaining",
+                remaining
+            ))
+        });
+    }
+
+    pub fn sol_log_64(arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64) {
+        super::sol_log(&format!(
+            "{:#x}, {:#x}, {:#x}, {:#x}, {:#x}",
+            arg1, arg2, arg3, arg4, arg5
+        ));
+    }
+}
+
+pub mod compute_units {
+    pub fn sol_remaining_compute_units() -> u64 {
+        super::consume()
+    }
+}
@@ -30,3 +30,46 @@ pub fn anchor_discriminant(input: &str) -> [u8; 8] {
     let result = hasher.finalize();
     result[..8].try_into().unwrap()
 }
+
+/// Expands to `PROBE`, for `concat!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! probe_name {
+    () => {
+        "sol_dev_probe"
+    };
+}
+
+/// Expands to `PROBE_INNER`, for `concat!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! probe_inner_name {
+    () => {
+        "sol_dev_probe_inner"
+    };
+}
+
+/// The frame logged by `#[compute_fn(probe)]` and `compute_probe!`, from which
+/// `sol-dev-cli parse --calibration auto` derives what logging a frame costs.
+pub const PROBE: &str = probe_name!();
+/// The empty frame inside `PROBE`.
+pub const PROBE_INNER: &str = probe_inner_name!();
+
+/// Tags a `sol_log_64` line as a frame logged by `#[compute_fn(log = "binary")]`,
+/// `sol_dev` in ASCII. The line is logged when the frame ends, with the id of the frame
+/// and the units remaining at its start and end.
+pub const FRAME_TAG: u64 = 0x0073_6f6c_5f64_6576;
+
+/// Calculates the id a frame is logged with in binary logs, the 64-bit FNV-1a hash of its name.
+///
+/// # Examples
+///
+/// ```
+/// let id = sol_dev_utils::frame_id("a");
+/// assert_eq!(id, 0xaf63_dc4c_8601_ec8c);
+/// ```
+pub fn frame_id(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}